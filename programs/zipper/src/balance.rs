@@ -0,0 +1,209 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, TokenAccount};
+use spl_token_2022::extension::{transfer_fee::TransferFeeAmount, StateWithExtensions};
+
+use crate::{stake::StakeAccount, ZipperError};
+
+/// A SOL, SPL token, mint or stake account provided to one of the zipper instructions.
+pub enum ZippedAccount {
+    /// A system program account
+    Sol { lamports: u64 },
+    /// A token account owned by either the SPL Token or the Token-2022 program
+    Token(TokenState),
+    /// A mint owned by either the SPL Token or the Token-2022 program
+    Mint(MintState),
+    /// An initialized stake program account
+    Stake(StakeAccount),
+    /// Any other account, only accepted when its balance is not checked
+    Other { lamports: u64, owner: Pubkey },
+}
+
+/// The fields of a token account that are common to the SPL Token and Token-2022 programs.
+pub struct TokenState {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub delegate: Option<Pubkey>,
+    pub delegated_amount: u64,
+    pub close_authority: Option<Pubkey>,
+    pub is_frozen: bool,
+    /// Transfer fees withheld in the account, `None` for SPL Token accounts
+    pub withheld: Option<u64>,
+}
+
+/// The fields of a mint that are common to the SPL Token and Token-2022 programs.
+pub struct MintState {
+    pub supply: u64,
+    pub decimals: u8,
+    pub mint_authority: Option<Pubkey>,
+    pub freeze_authority: Option<Pubkey>,
+}
+
+impl ZippedAccount {
+    /// Deserializes a SOL account, an SPL token or mint account, or a stake account. Any
+    /// other account is rejected.
+    pub fn load(acc: &AccountInfo) -> Result<ZippedAccount> {
+        if acc.owner == &token::ID {
+            let data = acc.data.borrow();
+            // Attempt to deserialize spl token account, falling back to a mint
+            if let Ok(token_account) = TokenAccount::try_deserialize(&mut &**data) {
+                return Ok(ZippedAccount::Token(TokenState {
+                    mint: token_account.mint,
+                    owner: token_account.owner,
+                    amount: token_account.amount,
+                    delegate: token_account.delegate.into(),
+                    delegated_amount: token_account.delegated_amount,
+                    close_authority: token_account.close_authority.into(),
+                    is_frozen: token_account.is_frozen(),
+                    withheld: None,
+                }));
+            }
+            let mint = Mint::try_deserialize(&mut &**data)
+                .map_err(|_| ZipperError::NonSOLOrSPLAccountProvided)?;
+            Ok(ZippedAccount::Mint(MintState {
+                supply: mint.supply,
+                decimals: mint.decimals,
+                mint_authority: mint.mint_authority.into(),
+                freeze_authority: mint.freeze_authority.into(),
+            }))
+        } else if acc.owner == &spl_token_2022::ID {
+            // Token-2022 accounts and mints share the base spl token layouts, optionally
+            // followed by extensions.
+            let data = acc.data.borrow();
+            if let Ok(token_account) =
+                StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)
+            {
+                // Transfer fees withheld in the account are not part of `amount` and cannot
+                // be spent by the owner, so they never count towards the balance.
+                let withheld = token_account
+                    .get_extension::<TransferFeeAmount>()
+                    .map(|fee| u64::from(fee.withheld_amount))
+                    .unwrap_or(0);
+                return Ok(ZippedAccount::Token(TokenState {
+                    mint: token_account.base.mint,
+                    owner: token_account.base.owner,
+                    amount: token_account.base.amount,
+                    delegate: token_account.base.delegate.into(),
+                    delegated_amount: token_account.base.delegated_amount,
+                    close_authority: token_account.base.close_authority.into(),
+                    is_frozen: token_account.base.is_frozen(),
+                    withheld: Some(withheld),
+                }));
+            }
+            let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)
+                .map_err(|_| ZipperError::NonSOLOrSPLAccountProvided)?;
+            Ok(ZippedAccount::Mint(MintState {
+                supply: mint.base.supply,
+                decimals: mint.base.decimals,
+                mint_authority: mint.base.mint_authority.into(),
+                freeze_authority: mint.base.freeze_authority.into(),
+            }))
+        } else if acc.owner == &anchor_lang::solana_program::stake::program::ID {
+            Ok(ZippedAccount::Stake(
+                StakeAccount::parse(&acc.data.borrow())
+                    .map_err(|_| ZipperError::NonSOLOrSPLAccountProvided)?,
+            ))
+        } else if acc.owner == &System::id() {
+            // If system program account just retrieve lamports
+            Ok(ZippedAccount::Sol {
+                lamports: acc.lamports(),
+            })
+        } else {
+            // None of the supported account kinds
+            Err(ZipperError::NonSOLOrSPLAccountProvided.into())
+        }
+    }
+
+    /// Deserializes an account like `load`, falling back to `ZippedAccount::Other` for any
+    /// account that `load` rejects.
+    pub fn load_any(acc: &AccountInfo) -> ZippedAccount {
+        ZippedAccount::load(acc).unwrap_or_else(|_| ZippedAccount::Other {
+            lamports: acc.lamports(),
+            owner: *acc.owner,
+        })
+    }
+
+    /// The token amount for token accounts, the supply for mints, the delegated stake that
+    /// has not been deactivated for stake accounts and the lamport balance for any other
+    /// account
+    pub fn balance(&self) -> u64 {
+        match self {
+            ZippedAccount::Stake(stake) => stake.delegated_stake(),
+            ZippedAccount::Mint(mint) => mint.supply,
+            ZippedAccount::Sol { lamports } | ZippedAccount::Other { lamports, .. } => *lamports,
+            ZippedAccount::Token(token_account) => token_account.amount,
+        }
+    }
+
+    /// A description of the account at `key` for logging
+    pub fn describe(&self, key: &Pubkey) -> String {
+        match self {
+            ZippedAccount::Sol { .. } => format!("sol addr {}", key),
+            ZippedAccount::Other { owner, .. } => format!("addr {}, owner {}", key, owner),
+            ZippedAccount::Mint(mint) => format!("mint addr {}, decimals {}", key, mint.decimals),
+            ZippedAccount::Stake(stake) => match &stake.delegation {
+                Some(delegation) if delegation.is_active() => {
+                    format!("stake addr {}, voter {}", key, delegation.voter)
+                }
+                Some(delegation) => format!(
+                    "stake addr {}, deactivated in epoch {}",
+                    key, delegation.deactivation_epoch
+                ),
+                None => format!("stake addr {}, undelegated", key),
+            },
+            ZippedAccount::Token(TokenState {
+                mint,
+                withheld: None,
+                ..
+            }) => format!("spl addr {}, mint {}", key, mint),
+            ZippedAccount::Token(TokenState {
+                mint,
+                withheld: Some(withheld),
+                ..
+            }) => format!(
+                "spl-2022 addr {}, mint {}, withheld {}",
+                key, mint, withheld
+            ),
+        }
+    }
+
+    /// The token account state, or an error if this is not a token account
+    pub fn token(&self) -> Result<&TokenState> {
+        match self {
+            ZippedAccount::Token(token_account) => Ok(token_account),
+            _ => Err(ZipperError::NotATokenAccount.into()),
+        }
+    }
+
+    /// The stake account, or an error if this is not a stake account
+    pub fn stake(&self) -> Result<&StakeAccount> {
+        match self {
+            ZippedAccount::Stake(stake) => Ok(stake),
+            _ => Err(ZipperError::NotAStakeAccount.into()),
+        }
+    }
+
+    /// The mint state, or an error if this is not a mint
+    pub fn mint(&self) -> Result<&MintState> {
+        match self {
+            ZippedAccount::Mint(mint) => Ok(mint),
+            _ => Err(ZipperError::NotAMintAccount.into()),
+        }
+    }
+}
+
+/// Retrieves the balance of a SOL or SPL token account along with a description of
+/// the account for logging.
+///
+/// For token accounts (owned by either the SPL Token or the Token-2022 program) this
+/// is the token amount, and for system program accounts this is the lamport balance.
+/// Any other account is rejected, including mints and stake accounts: their supply or
+/// delegated stake is not an amount held by the account that can be valued or spent.
+pub fn extract_balance(acc: &AccountInfo) -> Result<(u64, String)> {
+    match ZippedAccount::load(acc)? {
+        zipped @ (ZippedAccount::Sol { .. } | ZippedAccount::Token(_)) => {
+            Ok((zipped.balance(), zipped.describe(acc.key)))
+        }
+        _ => Err(ZipperError::NonSOLOrSPLAccountProvided.into()),
+    }
+}
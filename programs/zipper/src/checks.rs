@@ -0,0 +1,263 @@
+use anchor_lang::{prelude::*, solana_program::hash::hash};
+
+use crate::{balance::ZippedAccount, nonce, stake::StakeLockup, Comparison, ZipperError};
+
+/// An additional assertion on one of the zipped accounts, on top of its balance check.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountCheck {
+    /// Index of the account in the remaining accounts
+    pub index: u8,
+    pub check: Check,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// The token account holds this mint
+    Mint(Pubkey),
+    /// The token account is owned by this wallet
+    Owner(Pubkey),
+    /// The token account has no delegate
+    NoDelegate,
+    /// The token account has either no delegate, or this delegate approved for at most `amount`
+    DelegateAtMost { delegate: Pubkey, amount: u64 },
+    /// The token account has no close authority
+    NoCloseAuthority,
+    /// The token account is not frozen
+    NotFrozen,
+    /// The account is owned by this program
+    ProgramOwner(Pubkey),
+    /// The account data has exactly this length
+    DataLen(u64),
+    /// The sha256 hash of the account data in `offset..offset + len` is `hash`
+    DataHash {
+        offset: u32,
+        len: u32,
+        hash: [u8; 32],
+    },
+    /// The lamport balance of the account, regardless of its owner, satisfies `comparison`
+    /// against `amount`.
+    ///
+    /// Combined with `Comparison::Any` this checks only the lamports of e.g. a PDA or
+    /// program-owned escrow; combined with a token account balance check it checks both.
+    Lamports { comparison: Comparison, amount: u64 },
+    /// The mint has this many decimals
+    Decimals(u8),
+    /// The mint has this mint authority, or none
+    MintAuthority(Option<Pubkey>),
+    /// The mint has this freeze authority, or none
+    FreezeAuthority(Option<Pubkey>),
+    /// The stake account is delegated to this vote account, and its stake has not been
+    /// deactivated
+    Voter(Pubkey),
+    /// The stake account has this staker authority
+    Staker(Pubkey),
+    /// The stake account has this withdrawer authority
+    Withdrawer(Pubkey),
+    /// The stake account has exactly this lockup
+    Lockup(StakeLockup),
+    /// The token account is owned by `owner` and holds exactly one token of the mint
+    /// zipped at `mint_index`, which has 0 decimals and a supply of 1
+    Nft { mint_index: u8, owner: Pubkey },
+    /// The account is an initialized durable nonce account with this authority
+    NonceAuthority(Pubkey),
+}
+
+impl Check {
+    /// Builds a `Check::DataHash` for the bytes `offset..offset + len` of `data`,
+    /// e.g. using the account data fetched before the transaction is sent.
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn data_hash(data: &[u8], offset: u32, len: u32) -> Check {
+        let range = offset as usize..offset as usize + len as usize;
+        Check::DataHash {
+            offset,
+            len,
+            hash: hash(&data[range]).to_bytes(),
+        }
+    }
+
+    /// Runs this check on the account at `index`, where `accs` are all of the zipped
+    /// accounts and `accounts` their deserialized states. `index` must be in bounds.
+    pub fn verify(
+        &self,
+        accs: &[AccountInfo],
+        accounts: &[ZippedAccount],
+        index: usize,
+    ) -> Result<()> {
+        let (acc, account) = (&accs[index], &accounts[index]);
+        match *self {
+            Check::Mint(mint) => {
+                require_keys_eq!(account.token()?.mint, mint, ZipperError::MintMismatch)
+            }
+            Check::Owner(owner) => {
+                require_keys_eq!(account.token()?.owner, owner, ZipperError::OwnerMismatch)
+            }
+            Check::NoDelegate => {
+                require!(
+                    account.token()?.delegate.is_none(),
+                    ZipperError::UnexpectedDelegate
+                )
+            }
+            Check::DelegateAtMost { delegate, amount } => {
+                let token_account = account.token()?;
+                if let Some(actual_delegate) = token_account.delegate {
+                    require_keys_eq!(actual_delegate, delegate, ZipperError::UnexpectedDelegate);
+                    require_gte!(
+                        amount,
+                        token_account.delegated_amount,
+                        ZipperError::ExcessiveDelegatedAmount
+                    );
+                }
+            }
+            Check::NoCloseAuthority => {
+                require!(
+                    account.token()?.close_authority.is_none(),
+                    ZipperError::UnexpectedCloseAuthority
+                )
+            }
+            Check::NotFrozen => {
+                require!(!account.token()?.is_frozen, ZipperError::AccountFrozen)
+            }
+            Check::ProgramOwner(program) => {
+                require_keys_eq!(*acc.owner, program, ZipperError::ProgramOwnerMismatch)
+            }
+            Check::DataLen(len) => {
+                require_eq!(acc.data_len() as u64, len, ZipperError::DataLenMismatch)
+            }
+            Check::DataHash {
+                offset,
+                len,
+                hash: expected,
+            } => {
+                let data = acc.data.borrow();
+                let bytes = (offset as usize)
+                    .checked_add(len as usize)
+                    .and_then(|end| data.get(offset as usize..end))
+                    .ok_or(ZipperError::DataRangeOutOfBounds)?;
+                require!(
+                    hash(bytes).to_bytes() == expected,
+                    ZipperError::DataHashMismatch
+                )
+            }
+            Check::Lamports { comparison, amount } => {
+                let lamports = acc.lamports();
+                comparison.check(lamports, amount).map_err(|error| {
+                    msg!(
+                        "expected {} lamports {} for addr {}",
+                        lamports,
+                        comparison.describe(amount),
+                        acc.key
+                    );
+                    error
+                })?
+            }
+            Check::Decimals(decimals) => {
+                require_eq!(
+                    account.mint()?.decimals,
+                    decimals,
+                    ZipperError::DecimalsMismatch
+                )
+            }
+            Check::MintAuthority(mint_authority) => {
+                require!(
+                    account.mint()?.mint_authority == mint_authority,
+                    ZipperError::MintAuthorityMismatch
+                )
+            }
+            Check::FreezeAuthority(freeze_authority) => {
+                require!(
+                    account.mint()?.freeze_authority == freeze_authority,
+                    ZipperError::FreezeAuthorityMismatch
+                )
+            }
+            Check::Voter(voter) => {
+                let delegation = account
+                    .stake()?
+                    .active_delegation()
+                    .ok_or(ZipperError::VoterMismatch)?;
+                require_keys_eq!(delegation.voter, voter, ZipperError::VoterMismatch)
+            }
+            Check::Staker(staker) => {
+                require_keys_eq!(account.stake()?.staker, staker, ZipperError::StakerMismatch)
+            }
+            Check::Withdrawer(withdrawer) => {
+                require_keys_eq!(
+                    account.stake()?.withdrawer,
+                    withdrawer,
+                    ZipperError::WithdrawerMismatch
+                )
+            }
+            Check::Lockup(lockup) => {
+                require!(
+                    account.stake()?.lockup == lockup,
+                    ZipperError::LockupMismatch
+                )
+            }
+            Check::Nft { mint_index, owner } => {
+                let token_account = account.token()?;
+                let (mint_acc, mint) = accs
+                    .get(mint_index as usize)
+                    .zip(accounts.get(mint_index as usize))
+                    .ok_or(ZipperError::InvalidCheckIndex)?;
+                let mint = mint.mint()?;
+                require_keys_eq!(token_account.mint, *mint_acc.key, ZipperError::MintMismatch);
+                require_keys_eq!(token_account.owner, owner, ZipperError::OwnerMismatch);
+                require!(
+                    token_account.amount == 1 && mint.decimals == 0 && mint.supply == 1,
+                    ZipperError::NotAnNft
+                )
+            }
+            Check::NonceAuthority(authority) => {
+                require_keys_eq!(*acc.owner, System::id(), ZipperError::InvalidNonceAccount);
+                require_keys_eq!(
+                    nonce::authority(&acc.data.borrow())?,
+                    authority,
+                    ZipperError::NonceAuthorityMismatch
+                )
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A bound on the summed balances of several of the zipped accounts, e.g. native SOL
+/// plus a wrapped SOL token account, or several token accounts of the same mint.
+///
+/// The accounts in a group are still subject to their own comparisons, so these are
+/// usually `Comparison::AtLeast` with a zero balance.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BalanceGroup {
+    /// Indices of the accounts in the remaining accounts
+    pub indices: Vec<u8>,
+    pub comparison: Comparison,
+    pub amount: u64,
+}
+
+impl BalanceGroup {
+    /// Checks the summed balances of the accounts of this group, where `accs` are all of
+    /// the zipped accounts and `accounts` their deserialized states.
+    pub fn verify(&self, accs: &[AccountInfo], accounts: &[ZippedAccount]) -> Result<()> {
+        let mut total: u64 = 0;
+        let mut keys: Vec<&Pubkey> = Vec::with_capacity(self.indices.len());
+        for &index in &self.indices {
+            let (acc, account) = accs
+                .get(index as usize)
+                .zip(accounts.get(index as usize))
+                .ok_or(ZipperError::InvalidCheckIndex)?;
+            // Counting an account twice would inflate the total, even when it is zipped
+            // at two different indices
+            require!(!keys.contains(&acc.key), ZipperError::DuplicateGroupIndex);
+            keys.push(acc.key);
+            total = total.saturating_add(account.balance());
+        }
+        self.comparison.check(total, self.amount).map_err(|error| {
+            msg!(
+                "expected total {} {} for account indices {:?}",
+                total,
+                self.comparison.describe(self.amount),
+                self.indices
+            );
+            error
+        })
+    }
+}
@@ -0,0 +1,134 @@
+use anchor_client::solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig},
+};
+use anchor_client::solana_sdk::{account::Account, message::Message, transaction::Transaction};
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::Instruction};
+use solana_account_decoder::UiAccountEncoding;
+
+use super::{
+    load_account, payer_and_writable_keys, verify_instruction, Policy, PostBalance,
+    ZipperClientError,
+};
+use crate::balance::ZippedAccount;
+
+/// Zips a list of instructions with a `verify_with` instruction whose balances are filled from
+/// a simulation.
+///
+/// The instructions are simulated with `payer` as the fee payer, and the post-balances of
+/// all of their writable SOL and SPL token accounts are read from the simulated account
+/// states, so the payer's balance already has the transaction fee deducted. The balances
+/// are turned into bounds checked with `Comparison::AtLeast` by the `Policy`.
+///
+/// ```ignore
+/// let instructions = ZipperBuilder::new(&rpc, user.pubkey())
+///     .instruction(swap_ix)
+///     .policy(Policy::default().token_tolerance(Tolerance::BasisPoints(50)))
+///     .build()?;
+/// ```
+pub struct ZipperBuilder<'a> {
+    rpc: &'a RpcClient,
+    payer: Pubkey,
+    instructions: Vec<Instruction>,
+    policy: Policy,
+}
+
+impl<'a> ZipperBuilder<'a> {
+    pub fn new(rpc: &'a RpcClient, payer: Pubkey) -> Self {
+        ZipperBuilder {
+            rpc,
+            payer,
+            instructions: vec![],
+            policy: Policy::default(),
+        }
+    }
+
+    /// Appends an instruction to be zipped
+    pub fn instruction(mut self, instruction: Instruction) -> Self {
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// Appends several instructions to be zipped
+    pub fn instructions(mut self, instructions: impl IntoIterator<Item = Instruction>) -> Self {
+        self.instructions.extend(instructions);
+        self
+    }
+
+    /// Sets the policy turning simulated post-balances into bounds
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The payer followed by the writable accounts of the instructions, in order of
+    /// first appearance
+    pub fn writable_accounts(&self) -> Vec<Pubkey> {
+        payer_and_writable_keys(&self.instructions, &self.payer)
+    }
+
+    /// Simulates the instructions and returns the writable SOL and SPL token accounts
+    /// with their post-balances, as `verify` would read them. Accounts that are closed
+    /// or owned by other programs after the simulation are left out.
+    pub fn simulate(&self) -> Result<Vec<PostBalance>, ZipperClientError> {
+        let keys = self.writable_accounts();
+        let transaction =
+            Transaction::new_unsigned(Message::new(&self.instructions, Some(&self.payer)));
+        let result = self
+            .rpc
+            .simulate_transaction_with_config(
+                &transaction,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.rpc.commitment()),
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        addresses: keys.iter().map(Pubkey::to_string).collect(),
+                    }),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )?
+            .value;
+        if let Some(error) = result.err {
+            return Err(ZipperClientError::SimulationFailed {
+                error,
+                logs: result.logs.unwrap_or_default(),
+            });
+        }
+        let accounts = result.accounts.ok_or(ZipperClientError::MissingAccounts)?;
+        if accounts.len() != keys.len() {
+            return Err(ZipperClientError::MissingAccounts);
+        }
+
+        Ok(keys
+            .into_iter()
+            .zip(accounts)
+            .filter_map(|(key, account)| {
+                let mut account: Account = account?.decode()?;
+                let zipped = load_account(&key, &mut account)?;
+                let mint = match zipped {
+                    ZippedAccount::Sol { .. } => None,
+                    ZippedAccount::Token(ref token) => Some(token.mint),
+                    _ => return None,
+                };
+                Some(PostBalance {
+                    key,
+                    mint,
+                    balance: zipped.balance(),
+                })
+            })
+            .collect())
+    }
+
+    /// Simulates the instructions and returns them followed by the zipper instruction
+    pub fn build(self) -> Result<Vec<Instruction>, ZipperClientError> {
+        let post_balances = self.simulate()?;
+        let keys: Vec<Pubkey> = post_balances.iter().map(|post| post.key).collect();
+        let balances = self.policy.bounds(&self.payer, &post_balances);
+
+        let mut instructions = self.instructions;
+        instructions.push(verify_instruction(&keys, balances, vec![], vec![], vec![]));
+        Ok(instructions)
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+
+use anchor_client::solana_client::rpc_client::RpcClient;
+use anchor_client::solana_sdk::account::Account;
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::instruction::Instruction,
+};
+
+use super::{load_account, payer_and_writable_keys, ZipperClientError};
+use crate::{balance::ZippedAccount, AccountZipper};
+
+/// `getMultipleAccounts` returns at most this many accounts per request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Why an account was selected to be zipped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// The user's own system account
+    UserWallet,
+    /// A token account of `mint` owned by the user
+    UserTokenAccount { mint: Pubkey },
+}
+
+/// A writable account of the user found in the instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiscoveredAccount {
+    pub key: Pubkey,
+    pub reason: Reason,
+}
+
+impl fmt::Display for DiscoveredAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            Reason::UserWallet => write!(f, "{}: writable wallet of the user", self.key),
+            Reason::UserTokenAccount { mint } => write!(
+                f,
+                "{}: writable token account of mint {} owned by the user",
+                self.key, mint
+            ),
+        }
+    }
+}
+
+/// The accounts to zip, in order of first appearance in the instructions
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Discovery {
+    pub accounts: Vec<DiscoveredAccount>,
+}
+
+impl Discovery {
+    /// The keys of the accounts to zip, to be passed to `AccountZipper::zip_accounts`
+    pub fn keys(&self) -> Vec<Pubkey> {
+        self.accounts.iter().map(|account| account.key).collect()
+    }
+
+    /// The zipped account metas, see `AccountZipper::zip_accounts`
+    pub fn zip_accounts(&self) -> Vec<AccountMeta> {
+        AccountZipper::zip_accounts(&self.keys())
+    }
+
+    /// One line per account explaining why it is zipped
+    pub fn rationale(&self) -> Vec<String> {
+        self.accounts.iter().map(ToString::to_string).collect()
+    }
+}
+
+/// Finds the writable accounts of `user` in `instructions`: the user's system account
+/// and the token accounts owned by the user. Accounts are looked up with `get_account`,
+/// which returns `None` for accounts that do not exist.
+///
+/// `user` is the fee payer of the transaction, so the user's system account is always
+/// zipped first, even if the instructions only use it as a readonly signer. Writable
+/// accounts that belong to anyone else, or that do not exist yet, are left out.
+pub fn discover_with(
+    instructions: &[Instruction],
+    user: &Pubkey,
+    mut get_account: impl FnMut(&Pubkey) -> Option<Account>,
+) -> Discovery {
+    let mut accounts: Vec<DiscoveredAccount> = vec![];
+    for key in payer_and_writable_keys(instructions, user) {
+        let mut account = match get_account(&key) {
+            Some(account) => account,
+            None => continue,
+        };
+        let reason = match load_account(&key, &mut account) {
+            Some(ZippedAccount::Sol { .. }) if key == *user => Reason::UserWallet,
+            Some(ZippedAccount::Token(token)) if token.owner == *user => {
+                Reason::UserTokenAccount { mint: token.mint }
+            }
+            _ => continue,
+        };
+        accounts.push(DiscoveredAccount { key, reason });
+    }
+    Discovery { accounts }
+}
+
+/// Same as `discover_with`, fetching the accounts from `rpc`
+pub fn discover(
+    rpc: &RpcClient,
+    instructions: &[Instruction],
+    user: &Pubkey,
+) -> Result<Discovery, ZipperClientError> {
+    let keys = payer_and_writable_keys(instructions, user);
+    let mut fetched: Vec<(Pubkey, Account)> = vec![];
+    for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for (key, account) in chunk.iter().zip(rpc.get_multiple_accounts(chunk)?) {
+            if let Some(account) = account {
+                fetched.push((*key, account));
+            }
+        }
+    }
+    Ok(discover_with(instructions, user, |key| {
+        fetched
+            .iter()
+            .find(|(fetched_key, _)| fetched_key == key)
+            .map(|(_, account)| account.clone())
+    }))
+}
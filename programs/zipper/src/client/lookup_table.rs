@@ -0,0 +1,78 @@
+use anchor_client::solana_sdk::message::v0::{Message, MessageAddressTableLookup};
+use anchor_lang::prelude::Pubkey;
+
+use super::ZipperClientError;
+
+/// Size of the metadata preceding the addresses of a lookup table account
+pub const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+/// A lookup table holds at most this many addresses
+pub const LOOKUP_TABLE_MAX_ADDRESSES: usize = 256;
+
+/// Discriminant of an initialized lookup table account
+const LOOKUP_TABLE_TAG: u32 = 1;
+
+/// The addresses stored in an address lookup table account
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupTable {
+    pub key: Pubkey,
+    pub addresses: Vec<Pubkey>,
+}
+
+impl LookupTable {
+    /// Reads the addresses of the lookup table account `key` from its `data`
+    pub fn deserialize(key: Pubkey, data: &[u8]) -> Result<LookupTable, ZipperClientError> {
+        if data.len() < LOOKUP_TABLE_META_SIZE
+            || u32::from_le_bytes(data[0..4].try_into().unwrap()) != LOOKUP_TABLE_TAG
+            || (data.len() - LOOKUP_TABLE_META_SIZE) % 32 != 0
+        {
+            return Err(ZipperClientError::InvalidLookupTable(key));
+        }
+        let addresses = data[LOOKUP_TABLE_META_SIZE..]
+            .chunks_exact(32)
+            .map(|address| Pubkey::new_from_array(address.try_into().unwrap()))
+            .collect();
+        Ok(LookupTable { key, addresses })
+    }
+}
+
+/// All the accounts of a v0 `message` in the order its instructions index them: the
+/// static keys, then the accounts it loads from `tables`
+pub fn resolve_account_keys(
+    message: &Message,
+    tables: &[LookupTable],
+) -> Result<Vec<Pubkey>, ZipperClientError> {
+    let (writable, readonly) = resolve_lookups(&message.address_table_lookups, tables)?;
+    Ok([&message.account_keys[..], &writable[..], &readonly[..]].concat())
+}
+
+/// The accounts loaded by `lookups` from `tables`: the writable accounts of every lookup
+/// followed by the readonly accounts of every lookup, the order in which v0 messages
+/// index them after their static keys
+pub(crate) fn resolve_lookups(
+    lookups: &[MessageAddressTableLookup],
+    tables: &[LookupTable],
+) -> Result<(Vec<Pubkey>, Vec<Pubkey>), ZipperClientError> {
+    let mut writable: Vec<Pubkey> = vec![];
+    let mut readonly: Vec<Pubkey> = vec![];
+    for lookup in lookups {
+        let table = tables
+            .iter()
+            .find(|table| table.key == lookup.account_key)
+            .ok_or(ZipperClientError::MissingLookupTable(lookup.account_key))?;
+        let resolve = |index: &u8| {
+            table
+                .addresses
+                .get(*index as usize)
+                .copied()
+                .ok_or(ZipperClientError::InvalidLookupTable(table.key))
+        };
+        for index in &lookup.writable_indexes {
+            writable.push(resolve(index)?);
+        }
+        for index in &lookup.readonly_indexes {
+            readonly.push(resolve(index)?);
+        }
+    }
+    Ok((writable, readonly))
+}
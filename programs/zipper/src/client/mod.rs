@@ -0,0 +1,147 @@
+//! Client-side helpers for building zipper instructions, enabled with the `client` feature.
+
+mod builder;
+mod discovery;
+mod lookup_table;
+mod policy;
+mod transaction;
+mod v0;
+
+pub use builder::ZipperBuilder;
+pub use discovery::{discover, discover_with, DiscoveredAccount, Discovery, Reason};
+pub use lookup_table::{
+    resolve_account_keys, LookupTable, LOOKUP_TABLE_MAX_ADDRESSES, LOOKUP_TABLE_META_SIZE,
+};
+pub use policy::{Policy, PostBalance, Tolerance};
+pub use transaction::{zip_serialized_transaction, zip_transaction, ZippedTransaction};
+pub use v0::{compile_v0_message, zip_v0_message};
+
+use std::fmt;
+
+use anchor_client::solana_client::client_error::ClientError;
+use anchor_client::solana_sdk::{account::Account, transaction::TransactionError};
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{account_info::IntoAccountInfo, instruction::Instruction},
+    InstructionData,
+};
+
+use crate::{balance::ZippedAccount, AccountCheck, AccountZipper, BalanceGroup, Comparison, ID};
+
+/// Errors returned by the client-side helpers
+#[derive(Debug)]
+pub enum ZipperClientError {
+    /// An RPC request failed
+    Rpc(ClientError),
+    /// The instructions failed in simulation
+    SimulationFailed {
+        error: TransactionError,
+        logs: Vec<String>,
+    },
+    /// The simulation did not return the state of the requested accounts
+    MissingAccounts,
+    /// The transaction could not be (de)serialized or is malformed
+    InvalidTransaction,
+    /// The message loads accounts from a lookup table that was not provided
+    MissingLookupTable(Pubkey),
+    /// The lookup table account is malformed or lacks an index used by the message
+    InvalidLookupTable(Pubkey),
+    /// The message would reference more accounts than a `u8` can index
+    TooManyAccounts,
+    /// The program of the appended instruction is loaded from a lookup table
+    LoadedProgramId(Pubkey),
+    /// The appended instruction needs a signature or write access to an account that
+    /// the message does not grant
+    MissingPrivilege(Pubkey),
+}
+
+impl fmt::Display for ZipperClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZipperClientError::Rpc(error) => write!(f, "rpc error: {}", error),
+            ZipperClientError::SimulationFailed { error, .. } => {
+                write!(f, "simulation failed: {}", error)
+            }
+            ZipperClientError::MissingAccounts => {
+                write!(f, "simulation did not return the requested accounts")
+            }
+            ZipperClientError::InvalidTransaction => write!(f, "invalid transaction"),
+            ZipperClientError::MissingLookupTable(key) => {
+                write!(f, "lookup table {} was not provided", key)
+            }
+            ZipperClientError::InvalidLookupTable(key) => {
+                write!(f, "invalid lookup table {}", key)
+            }
+            ZipperClientError::TooManyAccounts => write!(f, "too many accounts in message"),
+            ZipperClientError::LoadedProgramId(key) => {
+                write!(f, "program {} is loaded from a lookup table", key)
+            }
+            ZipperClientError::MissingPrivilege(key) => {
+                write!(
+                    f,
+                    "message does not grant the privileges required for {}",
+                    key
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ZipperClientError {}
+
+impl From<ClientError> for ZipperClientError {
+    fn from(error: ClientError) -> Self {
+        ZipperClientError::Rpc(error)
+    }
+}
+
+/// Builds a `verify_with` instruction checking the zipped `keys` against `balances`, see
+/// `AccountZipper::zip_accounts`
+pub fn verify_instruction(
+    keys: &[Pubkey],
+    balances: Vec<u64>,
+    comparisons: Vec<Comparison>,
+    checks: Vec<AccountCheck>,
+    groups: Vec<BalanceGroup>,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: AccountZipper::zip_accounts(keys),
+        data: crate::instruction::VerifyWith {
+            balances,
+            comparisons,
+            checks,
+            groups,
+        }
+        .data(),
+    }
+}
+
+/// Deserializes an account fetched from an RPC node the same way the zipper program does
+pub(crate) fn load_account(key: &Pubkey, account: &mut Account) -> Option<ZippedAccount> {
+    ZippedAccount::load(&(key, account).into_account_info()).ok()
+}
+
+/// The writable accounts of the instructions, in order of first appearance
+pub(crate) fn writable_keys(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut keys: Vec<Pubkey> = vec![];
+    for meta in instructions.iter().flat_map(|ix| &ix.accounts) {
+        if meta.is_writable && !keys.contains(&meta.pubkey) {
+            keys.push(meta.pubkey);
+        }
+    }
+    keys
+}
+
+/// The fee `payer` followed by the writable accounts of `instructions`, in order of
+/// first appearance. The payer is always writable in the transaction, even when the
+/// instructions only use it as a readonly signer.
+pub(crate) fn payer_and_writable_keys(instructions: &[Instruction], payer: &Pubkey) -> Vec<Pubkey> {
+    let mut keys = vec![*payer];
+    keys.extend(
+        writable_keys(instructions)
+            .into_iter()
+            .filter(|key| key != payer),
+    );
+    keys
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+use anchor_lang::prelude::Pubkey;
+
+/// How far below its simulated post-balance an account may end up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tolerance {
+    /// An absolute amount, in lamports or base units of the token
+    Absolute(u64),
+    /// A fraction of the simulated post-balance, in basis points
+    BasisPoints(u16),
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance::Absolute(0)
+    }
+}
+
+impl Tolerance {
+    /// The lowest balance accepted for an account with the simulated post-balance
+    /// `balance`. Basis points are rounded in favour of the user.
+    pub fn apply(&self, balance: u64) -> u64 {
+        let slack = match *self {
+            Tolerance::Absolute(amount) => amount,
+            Tolerance::BasisPoints(bps) => {
+                (balance as u128 * bps.min(10_000) as u128 / 10_000) as u64
+            }
+        };
+        balance.saturating_sub(slack)
+    }
+}
+
+/// The simulated post-balance of a zipped account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PostBalance {
+    pub key: Pubkey,
+    /// The mint of a token account, `None` for a SOL account
+    pub mint: Option<Pubkey>,
+    pub balance: u64,
+}
+
+/// Turns simulated post-balances into the balances checked by `verify`.
+///
+/// By default no account may end up below its simulated post-balance. Tolerances can be
+/// set for SOL accounts, for token accounts of a given mint and for all other token
+/// accounts, and the fee payer may additionally spend up to `fee_allowance` lamports on
+/// transaction fees and rent that the simulation did not account for.
+///
+/// ```ignore
+/// let policy = Policy::default()
+///     .fee_allowance(10_000)
+///     .mint_tolerance(usdc_mint, Tolerance::BasisPoints(50));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Policy {
+    pub sol: Tolerance,
+    pub token: Tolerance,
+    pub mints: BTreeMap<Pubkey, Tolerance>,
+    pub fee_allowance: u64,
+}
+
+impl Policy {
+    /// Sets the tolerance of SOL accounts
+    pub fn sol_tolerance(mut self, tolerance: Tolerance) -> Self {
+        self.sol = tolerance;
+        self
+    }
+
+    /// Sets the tolerance of token accounts whose mint has no tolerance of its own
+    pub fn token_tolerance(mut self, tolerance: Tolerance) -> Self {
+        self.token = tolerance;
+        self
+    }
+
+    /// Sets the tolerance of token accounts of `mint`
+    pub fn mint_tolerance(mut self, mint: Pubkey, tolerance: Tolerance) -> Self {
+        self.mints.insert(mint, tolerance);
+        self
+    }
+
+    /// Sets the lamports the fee payer may spend on top of its SOL tolerance
+    pub fn fee_allowance(mut self, lamports: u64) -> Self {
+        self.fee_allowance = lamports;
+        self
+    }
+
+    /// The tolerance applied to an account holding `mint`, `None` for SOL
+    pub fn tolerance(&self, mint: Option<&Pubkey>) -> Tolerance {
+        match mint {
+            None => self.sol,
+            Some(mint) => self.mints.get(mint).copied().unwrap_or(self.token),
+        }
+    }
+
+    /// The lowest balance accepted for `post_balance`, with `payer` the fee payer
+    pub fn bound(&self, payer: &Pubkey, post_balance: &PostBalance) -> u64 {
+        let bound = self
+            .tolerance(post_balance.mint.as_ref())
+            .apply(post_balance.balance);
+        if post_balance.mint.is_none() && post_balance.key == *payer {
+            bound.saturating_sub(self.fee_allowance)
+        } else {
+            bound
+        }
+    }
+
+    /// The balances to pass to `verify` for `post_balances`, in the same order
+    pub fn bounds(&self, payer: &Pubkey, post_balances: &[PostBalance]) -> Vec<u64> {
+        post_balances
+            .iter()
+            .map(|post_balance| self.bound(payer, post_balance))
+            .collect()
+    }
+}
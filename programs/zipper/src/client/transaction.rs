@@ -0,0 +1,203 @@
+use anchor_client::solana_sdk::{
+    instruction::CompiledInstruction,
+    message::{MessageHeader, VersionedMessage},
+    packet::PACKET_DATA_SIZE,
+    sanitize::Sanitize,
+    signature::Signature,
+    transaction::VersionedTransaction,
+};
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::Instruction};
+
+use super::{lookup_table::resolve_lookups, LookupTable, ZipperClientError};
+
+/// Messages index their accounts with a `u8`
+pub(crate) const MAX_ACCOUNTS: usize = 256;
+
+/// A transaction with a zipper instruction appended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZippedTransaction {
+    /// The zipped transaction. Its signatures are reset, since the message changed, and
+    /// must be provided again by the same signers.
+    pub transaction: VersionedTransaction,
+    /// The serialized size of the original transaction, in bytes
+    pub original_size: usize,
+    /// The serialized size of the zipped transaction, in bytes
+    pub zipped_size: usize,
+}
+
+impl ZippedTransaction {
+    /// The bytes added by zipping the transaction
+    pub fn overhead(&self) -> usize {
+        self.zipped_size.saturating_sub(self.original_size)
+    }
+
+    /// Whether the zipped transaction still fits in a single packet
+    pub fn fits_in_packet(&self) -> bool {
+        self.zipped_size <= PACKET_DATA_SIZE
+    }
+}
+
+/// Same as `zip_transaction`, for a bincode-serialized legacy or v0 transaction
+pub fn zip_serialized_transaction(
+    transaction: &[u8],
+    lookup_tables: &[LookupTable],
+    zipper_instruction: &Instruction,
+) -> Result<ZippedTransaction, ZipperClientError> {
+    let transaction: VersionedTransaction =
+        bincode::deserialize(transaction).map_err(|_| ZipperClientError::InvalidTransaction)?;
+    zip_transaction(&transaction, lookup_tables, zipper_instruction)
+}
+
+/// Appends `zipper_instruction` to a legacy or v0 transaction, resolving the accounts
+/// loaded by a v0 message from `lookup_tables`.
+///
+/// Accounts of the zipper instruction that the message already loads, statically or
+/// through a lookup table, are referenced where they are. Any other account is added as
+/// a readonly, unsigned static key, and the indexes of the accounts loaded from lookup
+/// tables are shifted accordingly. The signers and their order are left unchanged, so
+/// the zipper instruction cannot require a signature or write access the message does
+/// not already grant.
+///
+/// The transaction usually comes from an untrusted dApp, so it is sanitized first: a
+/// malformed header or an out-of-range account index is an `InvalidTransaction`.
+pub fn zip_transaction(
+    transaction: &VersionedTransaction,
+    lookup_tables: &[LookupTable],
+    zipper_instruction: &Instruction,
+) -> Result<ZippedTransaction, ZipperClientError> {
+    transaction
+        .sanitize()
+        .map_err(|_| ZipperClientError::InvalidTransaction)?;
+    let mut message = transaction.message.clone();
+    match &mut message {
+        VersionedMessage::Legacy(message) => append_instruction(
+            &mut message.header,
+            &mut message.account_keys,
+            &mut message.instructions,
+            (&[][..], &[][..]),
+            zipper_instruction,
+        )?,
+        VersionedMessage::V0(message) => {
+            let (writable, readonly) =
+                resolve_lookups(&message.address_table_lookups, lookup_tables)?;
+            append_instruction(
+                &mut message.header,
+                &mut message.account_keys,
+                &mut message.instructions,
+                (&writable, &readonly),
+                zipper_instruction,
+            )?
+        }
+    }
+
+    let zipped = VersionedTransaction {
+        signatures: vec![Signature::default(); transaction.signatures.len()],
+        message,
+    };
+    Ok(ZippedTransaction {
+        original_size: serialized_size(transaction)?,
+        zipped_size: serialized_size(&zipped)?,
+        transaction: zipped,
+    })
+}
+
+/// Compiles `instruction` into a message with the given static keys and accounts loaded
+/// from lookup tables, adding the accounts it is missing as readonly, unsigned static keys
+pub(crate) fn append_instruction(
+    header: &mut MessageHeader,
+    static_keys: &mut Vec<Pubkey>,
+    instructions: &mut Vec<CompiledInstruction>,
+    (loaded_writable, loaded_readonly): (&[Pubkey], &[Pubkey]),
+    instruction: &Instruction,
+) -> Result<(), ZipperClientError> {
+    // Programs cannot be invoked through a lookup table
+    if loaded_writable.contains(&instruction.program_id)
+        || loaded_readonly.contains(&instruction.program_id)
+    {
+        return Err(ZipperClientError::LoadedProgramId(instruction.program_id));
+    }
+
+    let mut missing: Vec<Pubkey> = vec![];
+    let keys = std::iter::once(&instruction.program_id)
+        .chain(instruction.accounts.iter().map(|meta| &meta.pubkey));
+    for key in keys {
+        if !static_keys.contains(key)
+            && !loaded_writable.contains(key)
+            && !loaded_readonly.contains(key)
+            && !missing.contains(key)
+        {
+            missing.push(*key);
+        }
+    }
+    let num_readonly_unsigned = missing.len() + header.num_readonly_unsigned_accounts as usize;
+    if static_keys.len() + missing.len() + loaded_writable.len() + loaded_readonly.len()
+        > MAX_ACCOUNTS
+        || num_readonly_unsigned > u8::MAX as usize
+    {
+        return Err(ZipperClientError::TooManyAccounts);
+    }
+
+    // New keys go at the end of the static keys, where the readonly unsigned accounts
+    // are, which moves the accounts loaded from lookup tables
+    let shift = missing.len() as u8;
+    let num_static_keys = static_keys.len();
+    for compiled in instructions.iter_mut() {
+        for index in compiled.accounts.iter_mut() {
+            if *index as usize >= num_static_keys {
+                *index += shift;
+            }
+        }
+    }
+    static_keys.extend(missing);
+    header.num_readonly_unsigned_accounts = num_readonly_unsigned as u8;
+
+    let num_static_keys = static_keys.len();
+    let num_signed = header.num_required_signatures as usize;
+    let num_writable_signed = num_signed - header.num_readonly_signed_accounts as usize;
+    let num_writable_unsigned = num_static_keys - num_readonly_unsigned;
+    let compile = |key: &Pubkey, is_signer: bool, is_writable: bool| {
+        let position = |keys: &[Pubkey]| keys.iter().position(|k| k == key);
+        let (index, signed, writable) = if let Some(index) = position(&static_keys[..]) {
+            let signed = index < num_signed;
+            let writable = if signed {
+                index < num_writable_signed
+            } else {
+                index < num_writable_unsigned
+            };
+            (index, signed, writable)
+        } else if let Some(index) = position(loaded_writable) {
+            (num_static_keys + index, false, true)
+        } else if let Some(index) = position(loaded_readonly) {
+            (
+                num_static_keys + loaded_writable.len() + index,
+                false,
+                false,
+            )
+        } else {
+            return Err(ZipperClientError::MissingPrivilege(*key));
+        };
+        if (is_signer && !signed) || (is_writable && !writable) {
+            return Err(ZipperClientError::MissingPrivilege(*key));
+        }
+        Ok(index as u8)
+    };
+
+    let program_id_index = compile(&instruction.program_id, false, false)?;
+    let accounts = instruction
+        .accounts
+        .iter()
+        .map(|meta| compile(&meta.pubkey, meta.is_signer, meta.is_writable))
+        .collect::<Result<Vec<u8>, ZipperClientError>>()?;
+    instructions.push(CompiledInstruction {
+        program_id_index,
+        accounts,
+        data: instruction.data.clone(),
+    });
+    Ok(())
+}
+
+fn serialized_size(transaction: &VersionedTransaction) -> Result<usize, ZipperClientError> {
+    bincode::serialized_size(transaction)
+        .map(|size| size as usize)
+        .map_err(|_| ZipperClientError::InvalidTransaction)
+}
@@ -0,0 +1,170 @@
+use anchor_client::solana_sdk::{
+    hash::Hash,
+    instruction::CompiledInstruction,
+    message::{
+        v0::{Message, MessageAddressTableLookup},
+        MessageHeader,
+    },
+};
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::Instruction};
+
+use super::{
+    lookup_table::LOOKUP_TABLE_MAX_ADDRESSES, transaction::MAX_ACCOUNTS, LookupTable,
+    ZipperClientError,
+};
+
+/// How a key is used by the instructions of a message
+#[derive(Clone, Copy, Default)]
+struct KeyMeta {
+    is_signer: bool,
+    is_writable: bool,
+    is_invoked: bool,
+}
+
+/// Compiles `instructions` followed by `zipper_instruction` into a v0 message, see
+/// `compile_v0_message`. Zipped accounts found in `lookup_tables` are loaded through
+/// them rather than added to the static keys, and accounts used by both the
+/// instructions and the zipper instruction are only loaded once.
+pub fn zip_v0_message(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    zipper_instruction: &Instruction,
+    lookup_tables: &[LookupTable],
+    recent_blockhash: Hash,
+) -> Result<Message, ZipperClientError> {
+    let mut instructions = instructions.to_vec();
+    instructions.push(zipper_instruction.clone());
+    compile_v0_message(payer, &instructions, lookup_tables, recent_blockhash)
+}
+
+/// Compiles `instructions` into a v0 message paid for by `payer`.
+///
+/// Accounts that are neither signers nor invoked programs are loaded from the first of
+/// `lookup_tables` that holds them, with the writable and readonly accounts of each
+/// table referenced by a single lookup. Every other account is a static key, ordered by
+/// signer and writable status and then by first appearance, with the payer first.
+pub fn compile_v0_message(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[LookupTable],
+    recent_blockhash: Hash,
+) -> Result<Message, ZipperClientError> {
+    let mut keys: Vec<(Pubkey, KeyMeta)> = vec![(
+        *payer,
+        KeyMeta {
+            is_signer: true,
+            is_writable: true,
+            is_invoked: false,
+        },
+    )];
+    for instruction in instructions {
+        key_meta(&mut keys, &instruction.program_id).is_invoked = true;
+        for account in &instruction.accounts {
+            let meta = key_meta(&mut keys, &account.pubkey);
+            meta.is_signer |= account.is_signer;
+            meta.is_writable |= account.is_writable;
+        }
+    }
+
+    // Move the accounts found in the lookup tables out of the static keys. The loaded
+    // accounts are indexed in the order of the lookups, writable accounts first
+    let mut address_table_lookups: Vec<MessageAddressTableLookup> = vec![];
+    let mut loaded_writable: Vec<Pubkey> = vec![];
+    let mut loaded_readonly: Vec<Pubkey> = vec![];
+    for table in lookup_tables {
+        let mut lookup = MessageAddressTableLookup {
+            account_key: table.key,
+            writable_indexes: vec![],
+            readonly_indexes: vec![],
+        };
+        keys.retain(|(key, meta)| {
+            if meta.is_signer || meta.is_invoked {
+                return true;
+            }
+            let index = match table
+                .addresses
+                .iter()
+                .take(LOOKUP_TABLE_MAX_ADDRESSES)
+                .position(|address| address == key)
+            {
+                Some(index) => index as u8,
+                None => return true,
+            };
+            if meta.is_writable {
+                lookup.writable_indexes.push(index);
+                loaded_writable.push(*key);
+            } else {
+                lookup.readonly_indexes.push(index);
+                loaded_readonly.push(*key);
+            }
+            false
+        });
+        if !lookup.writable_indexes.is_empty() || !lookup.readonly_indexes.is_empty() {
+            address_table_lookups.push(lookup);
+        }
+    }
+
+    if keys.len() + loaded_writable.len() + loaded_readonly.len() > MAX_ACCOUNTS {
+        return Err(ZipperClientError::TooManyAccounts);
+    }
+
+    let group = |is_signer: bool, is_writable: bool| -> Vec<Pubkey> {
+        keys.iter()
+            .filter(|(_, meta)| meta.is_signer == is_signer && meta.is_writable == is_writable)
+            .map(|(key, _)| *key)
+            .collect()
+    };
+    let (writable_signed, readonly_signed) = (group(true, true), group(true, false));
+    let (writable_unsigned, readonly_unsigned) = (group(false, true), group(false, false));
+    let header = MessageHeader {
+        num_required_signatures: (writable_signed.len() + readonly_signed.len()) as u8,
+        num_readonly_signed_accounts: readonly_signed.len() as u8,
+        num_readonly_unsigned_accounts: readonly_unsigned.len() as u8,
+    };
+    let account_keys: Vec<Pubkey> = [
+        writable_signed,
+        readonly_signed,
+        writable_unsigned,
+        readonly_unsigned,
+    ]
+    .concat();
+
+    let all_keys = [
+        &account_keys[..],
+        &loaded_writable[..],
+        &loaded_readonly[..],
+    ]
+    .concat();
+    let index = |key: &Pubkey| all_keys.iter().position(|k| k == key).unwrap() as u8;
+    let instructions = instructions
+        .iter()
+        .map(|instruction| CompiledInstruction {
+            program_id_index: index(&instruction.program_id),
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| index(&meta.pubkey))
+                .collect(),
+            data: instruction.data.clone(),
+        })
+        .collect();
+
+    Ok(Message {
+        header,
+        account_keys,
+        recent_blockhash,
+        instructions,
+        address_table_lookups,
+    })
+}
+
+fn key_meta<'a>(keys: &'a mut Vec<(Pubkey, KeyMeta)>, key: &Pubkey) -> &'a mut KeyMeta {
+    let position = match keys.iter().position(|(k, _)| k == key) {
+        Some(position) => position,
+        None => {
+            keys.push((*key, KeyMeta::default()));
+            keys.len() - 1
+        }
+    };
+    &mut keys[position].1
+}
@@ -0,0 +1,29 @@
+//! Slot and unix timestamp bounds on when a transaction executes.
+
+use anchor_lang::prelude::*;
+
+use crate::ZipperError;
+
+/// Checks that `clock` is no later than `max_slot` and `max_unix_timestamp`, and no
+/// earlier than `min_slot`. All bounds are inclusive and optional.
+pub fn check(
+    clock: &Clock,
+    max_slot: Option<u64>,
+    max_unix_timestamp: Option<i64>,
+    min_slot: Option<u64>,
+) -> Result<()> {
+    if let Some(max_slot) = max_slot {
+        require_gte!(max_slot, clock.slot, ZipperError::SlotDeadlineExceeded);
+    }
+    if let Some(max_unix_timestamp) = max_unix_timestamp {
+        require_gte!(
+            max_unix_timestamp,
+            clock.unix_timestamp,
+            ZipperError::TimestampDeadlineExceeded
+        );
+    }
+    if let Some(min_slot) = min_slot {
+        require_gte!(clock.slot, min_slot, ZipperError::SlotTooEarly);
+    }
+    Ok(())
+}
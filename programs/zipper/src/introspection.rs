@@ -0,0 +1,102 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::Instruction,
+        sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+    },
+    Discriminator,
+};
+
+use crate::{packed::PACKED_VERIFY_TAG, ZipperError, ID};
+
+/// The number of top-level instructions in the transaction, read from the
+/// Instructions sysvar account `instructions`.
+pub fn num_instructions(instructions: &AccountInfo) -> Result<usize> {
+    require_keys_eq!(
+        *instructions.key,
+        anchor_lang::solana_program::sysvar::instructions::ID,
+        ZipperError::InvalidInstructionsSysvar
+    );
+    let data = instructions.data.borrow();
+    require!(data.len() >= 2, ZipperError::InvalidInstructionsSysvar);
+    Ok(u16::from_le_bytes([data[0], data[1]]) as usize)
+}
+
+/// The index of the currently executing top-level instruction
+pub fn current_index(instructions: &AccountInfo) -> Result<usize> {
+    Ok(load_current_index_checked(instructions)? as usize)
+}
+
+/// The top-level instruction at `index`
+pub fn instruction_at(instructions: &AccountInfo, index: usize) -> Result<Instruction> {
+    Ok(load_instruction_at_checked(index, instructions)?)
+}
+
+/// Checks that every top-level instruction in the transaction targets one of the
+/// `allowed_programs` or the zipper program itself.
+pub fn assert_allowed_programs(
+    instructions: &AccountInfo,
+    allowed_programs: &[Pubkey],
+) -> Result<()> {
+    for i in 0..num_instructions(instructions)? {
+        let program_id = instruction_at(instructions, i)?.program_id;
+        if program_id != ID && !allowed_programs.contains(&program_id) {
+            msg!("instruction {} invokes program {}", i, program_id);
+            return Err(ZipperError::ProgramNotAllowed.into());
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the currently executing top-level instruction invokes the zipper program
+/// directly, rather than through a CPI from another program.
+pub fn assert_top_level(instructions: &AccountInfo) -> Result<()> {
+    let current = instruction_at(instructions, current_index(instructions)?)?;
+    require_keys_eq!(current.program_id, ID, ZipperError::NotTopLevelInstruction);
+    Ok(())
+}
+
+/// Whether `instruction` is a zipper instruction that only asserts on the state of the
+/// transaction, such as `verify` or the packed verify. `snapshot`, which pays rent, and
+/// `verify_dry_run`, which never fails, are not.
+pub fn is_verify_instruction(instruction: &Instruction) -> bool {
+    if instruction.program_id != ID {
+        return false;
+    }
+    if instruction.data.starts_with(&PACKED_VERIFY_TAG) {
+        return true;
+    }
+    let discriminators = [
+        crate::instruction::Verify::discriminator(),
+        crate::instruction::VerifyWith::discriminator(),
+        crate::instruction::VerifyPrograms::discriminator(),
+        crate::instruction::VerifyLastInstruction::discriminator(),
+        crate::instruction::VerifyDeadline::discriminator(),
+        crate::instruction::VerifyValue::discriminator(),
+        crate::instruction::VerifyPoolPrice::discriminator(),
+        crate::instruction::VerifyDelta::discriminator(),
+    ];
+    instruction
+        .data
+        .get(..8)
+        .map_or(false, |data| discriminators.iter().any(|d| d == data))
+}
+
+/// Checks that the currently executing top-level instruction is the last one in the
+/// transaction or, if `allow_zipper` is set, that all later ones are zipper verify
+/// instructions, see `is_verify_instruction`.
+pub fn assert_last_instruction(instructions: &AccountInfo, allow_zipper: bool) -> Result<()> {
+    let current = current_index(instructions)?;
+    for i in current + 1..num_instructions(instructions)? {
+        let instruction = instruction_at(instructions, i)?;
+        if !(allow_zipper && is_verify_instruction(&instruction)) {
+            msg!(
+                "instruction {} after the zipper invokes program {}",
+                i,
+                instruction.program_id
+            );
+            return Err(ZipperError::NotLastInstruction.into());
+        }
+    }
+    Ok(())
+}
@@ -1,7 +1,23 @@
-use anchor_lang::prelude::*;
-use anchor_spl::token::TokenAccount;
+use anchor_lang::{prelude::*, solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE};
 use solana_security_txt::security_txt;
 
+pub mod balance;
+pub mod checks;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod deadline;
+pub mod introspection;
+pub mod nonce;
+pub mod oracle;
+pub mod packed;
+pub mod pool;
+pub mod stake;
+pub mod verification;
+
+use balance::{extract_balance, ZippedAccount};
+pub use checks::{AccountCheck, BalanceGroup, Check};
+use verification::verify_accounts;
+
 declare_id!("Z1PrGTgZp5Q1WKewjF4XaTW2nHvNxvbxs7qW8p9qz5U");
 
 security_txt! {
@@ -16,56 +32,215 @@ pub mod zipper {
     use super::*;
 
     pub fn verify(ctx: Context<VerifyAccounts>, balances: Vec<u64>) -> Result<()> {
-        // Check that the number of accounts provided is correct
+        verify_accounts(ctx.remaining_accounts, &balances, &[], &[], &[], false)
+    }
+
+    /// Same as `verify`, with a comparison per account instead of `Comparison::AtLeast`,
+    /// additional `checks` on individual accounts and bounds on the summed balances of
+    /// `groups` of accounts.
+    pub fn verify_with(
+        ctx: Context<VerifyAccounts>,
+        balances: Vec<u64>,
+        comparisons: Vec<Comparison>,
+        checks: Vec<AccountCheck>,
+        groups: Vec<BalanceGroup>,
+    ) -> Result<()> {
+        verify_accounts(
+            ctx.remaining_accounts,
+            &balances,
+            &comparisons,
+            &checks,
+            &groups,
+            false,
+        )
+    }
+
+    /// Same as `verify_with`, but never fails on the state of the accounts: failed checks
+    /// are only logged. Used in simulations to read the actual balances from the return data.
+    pub fn verify_dry_run(
+        ctx: Context<VerifyAccounts>,
+        balances: Vec<u64>,
+        comparisons: Vec<Comparison>,
+        checks: Vec<AccountCheck>,
+        groups: Vec<BalanceGroup>,
+    ) -> Result<()> {
+        verify_accounts(
+            ctx.remaining_accounts,
+            &balances,
+            &comparisons,
+            &checks,
+            &groups,
+            true,
+        )
+    }
+
+    /// Checks that every top-level instruction in the transaction targets one of the
+    /// `allowed_programs` or the zipper program itself.
+    pub fn verify_programs(
+        ctx: Context<IntrospectionAccounts>,
+        allowed_programs: Vec<Pubkey>,
+    ) -> Result<()> {
+        introspection::assert_allowed_programs(&ctx.accounts.instructions, &allowed_programs)
+    }
+
+    /// Checks that this is the last top-level instruction in the transaction or, if
+    /// `allow_zipper` is set, that it is only followed by other zipper verify instructions,
+    /// so that no instruction can move funds after the balances are checked.
+    pub fn verify_last_instruction(
+        ctx: Context<IntrospectionAccounts>,
+        allow_zipper: bool,
+    ) -> Result<()> {
+        introspection::assert_last_instruction(&ctx.accounts.instructions, allow_zipper)
+    }
+
+    /// Checks that the transaction executes no later than `max_slot` and
+    /// `max_unix_timestamp`, and no earlier than `min_slot`. All bounds are inclusive
+    /// and optional.
+    pub fn verify_deadline(
+        _ctx: Context<VerifyAccounts>,
+        max_slot: Option<u64>,
+        max_unix_timestamp: Option<i64>,
+        min_slot: Option<u64>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        msg!(
+            "executing at slot {}, unix timestamp {}",
+            clock.slot,
+            clock.unix_timestamp
+        );
+        deadline::check(&clock, max_slot, max_unix_timestamp, min_slot)
+    }
+
+    /// Checks that the total value of a portfolio is at least `min_value`, in units of
+    /// `10^value_expo` of the oracles' quote currency.
+    ///
+    /// The remaining accounts are pairs of a SOL or SPL token account followed by the
+    /// price account of its asset, owned by `oracle_program`, with `decimals[i]` the
+    /// decimals of the i-th asset (9 for SOL). Each price must be trading, published at
+    /// most `max_staleness` slots ago, and have a confidence interval of at most
+    /// `max_confidence_bps` basis points; balances are valued at the lower end of it.
+    pub fn verify_value(
+        ctx: Context<VerifyAccounts>,
+        min_value: u64,
+        value_expo: i32,
+        decimals: Vec<u8>,
+        oracle_program: Pubkey,
+        max_staleness: u64,
+        max_confidence_bps: u16,
+    ) -> Result<()> {
+        oracle::check_value(
+            ctx.remaining_accounts,
+            Clock::get()?.slot,
+            min_value,
+            value_expo,
+            &decimals,
+            &oracle_program,
+            max_staleness,
+            max_confidence_bps,
+        )
+    }
+
+    /// Checks that the price of a pool, derived from its two token reserves passed as the
+    /// remaining accounts, lies within `[min_price, max_price]`. Prices are Q64.64 fixed
+    /// point amounts of the second reserve's token per unit of the first's, see `pool`.
+    ///
+    /// Placed before a swap, this makes the transaction fail if the pool has been moved.
+    pub fn verify_pool_price(
+        ctx: Context<VerifyAccounts>,
+        min_price: u128,
+        max_price: u128,
+    ) -> Result<()> {
+        let (reserve_a, reserve_b) = match ctx.remaining_accounts {
+            [reserve_a, reserve_b] => (reserve_a, reserve_b),
+            _ => return Err(ZipperError::InvalidNumberOfAccountsOrBalances.into()),
+        };
+        let amount_a = ZippedAccount::load(reserve_a)?.token()?.amount;
+        let amount_b = ZippedAccount::load(reserve_b)?.token()?.amount;
+
+        let price = pool::price(amount_a, amount_b).ok_or(ZipperError::EmptyPoolReserve)?;
+        msg!(
+            "expected pool price {} in [{}, {}] for reserves {} and {}",
+            price,
+            min_price,
+            max_price,
+            amount_a,
+            amount_b
+        );
+        if price < min_price || price > max_price {
+            return Err(error!(ZipperError::PoolPriceOutOfRange)
+                .with_values((price, format!("[{}, {}]", min_price, max_price))));
+        }
+        Ok(())
+    }
+
+    /// Entry point for the packed, zero-copy variant of `verify`. Instruction data
+    /// starting with `packed::PACKED_VERIFY_TAG` is parsed in place without allocating,
+    /// see the `packed` module for the layout.
+    pub fn fallback(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> Result<()> {
+        match data.strip_prefix(&packed::PACKED_VERIFY_TAG[..]) {
+            Some(data) => packed::verify(accounts, data),
+            None => Err(anchor_lang::error::ErrorCode::InstructionFallbackNotFound.into()),
+        }
+    }
+
+    /// Records the current balances of the remaining accounts so that a later
+    /// `verify_delta` in the same transaction can assert on how they changed.
+    pub fn snapshot(ctx: Context<TakeSnapshot>, num_accounts: u8) -> Result<()> {
+        introspection::assert_top_level(&ctx.accounts.instructions)?;
         require_eq!(
             ctx.remaining_accounts.len(),
-            balances.len(),
+            num_accounts as usize,
             ZipperError::InvalidNumberOfAccountsOrBalances
         );
 
-        // Check that all accounts provided are either token accounts or
-        // system program accounts and extract balances
-        let actual_balances: Vec<(u64, String)> = ctx
+        let snapshot = &mut ctx.accounts.snapshot;
+        snapshot.authority = ctx.accounts.authority.key();
+        snapshot.entries = ctx
             .remaining_accounts
-            .into_iter()
+            .iter()
             .map(|acc| {
-                if let Ok(token_account) = TokenAccount::try_deserialize(&mut &**acc.data.borrow())
-                {
-                    // Attempt to deserialize spl token account and get balance + mint
-                    return Ok((
-                        token_account.amount,
-                        format!(
-                            "spl addr {}, mint {}",
-                            acc.key.to_string(),
-                            token_account.mint
-                        ),
-                    ));
-                } else if acc.owner == &System::id() {
-                    // If system program account just retrieve lamports
-                    Ok((acc.lamports(), format!("sol addr {}", acc.key.to_string())))
-                } else {
-                    // Neither SPL or System Program Account
-                    Err(ZipperError::NonSOLOrSPLAccountProvided.into())
-                }
+                let (balance, description) = extract_balance(acc)?;
+                msg!("snapshot {} for {}", balance, description);
+                Ok(SnapshotEntry {
+                    key: acc.key(),
+                    balance,
+                })
             })
-            .collect::<Result<Vec<(u64, String)>>>()
-            .map_err(|_| ZipperError::NonSOLOrSPLAccountProvided)?;
-
-        // Check Balances
-        for i in 0..actual_balances.len() {
-            if actual_balances[i].0 < balances[i] {
-                panic!(
-                    "expected {} >= {} for {}",
-                    actual_balances[i].0, balances[i], actual_balances[i].1,
-                );
-            } else {
-                msg!(
-                    "expected {} >= {} for {}",
-                    actual_balances[i].0,
-                    balances[i],
-                    actual_balances[i].1,
-                )
-            }
+            .collect::<Result<Vec<SnapshotEntry>>>()?;
+        Ok(())
+    }
+
+    /// Checks that the balance of each remaining account changed by at least `deltas[i]`
+    /// since the `snapshot`, and closes the snapshot account.
+    pub fn verify_delta(ctx: Context<VerifyDelta>, deltas: Vec<i64>) -> Result<()> {
+        introspection::assert_top_level(&ctx.accounts.instructions)?;
+        let entries = &ctx.accounts.snapshot.entries;
+
+        // Check that the number of accounts provided is correct
+        require_eq!(
+            ctx.remaining_accounts.len(),
+            entries.len(),
+            ZipperError::InvalidNumberOfAccountsOrBalances
+        );
+        require_eq!(
+            deltas.len(),
+            entries.len(),
+            ZipperError::InvalidNumberOfAccountsOrBalances
+        );
+
+        for ((acc, entry), &delta) in ctx.remaining_accounts.iter().zip(entries).zip(&deltas) {
+            // The accounts must be provided in the same order as they were snapshotted
+            require_keys_eq!(acc.key(), entry.key, ZipperError::SnapshotAccountMismatch);
+
+            let (balance, description) = extract_balance(acc)?;
+            let change = balance as i128 - entry.balance as i128;
+            msg!(
+                "expected change {} >= {} for {}",
+                change,
+                delta,
+                description
+            );
+            require!(change >= delta as i128, ZipperError::InsufficientDelta);
         }
         Ok(())
     }
@@ -74,6 +249,92 @@ pub mod zipper {
 #[derive(Accounts)]
 pub struct VerifyAccounts {}
 
+#[derive(Accounts)]
+pub struct IntrospectionAccounts<'info> {
+    /// CHECK: the address is checked to be the Instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+pub const SNAPSHOT_SEED: &[u8] = b"snapshot";
+
+#[derive(Accounts)]
+#[instruction(num_accounts: u8)]
+pub struct TakeSnapshot<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = BalanceSnapshot::space(num_accounts as usize)?,
+        seeds = [SNAPSHOT_SEED, authority.key().as_ref()],
+        bump,
+    )]
+    pub snapshot: Account<'info, BalanceSnapshot>,
+    /// CHECK: the address is checked to be the Instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyDelta<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        close = authority,
+        has_one = authority,
+        seeds = [SNAPSHOT_SEED, authority.key().as_ref()],
+        bump,
+    )]
+    pub snapshot: Account<'info, BalanceSnapshot>,
+    /// CHECK: the address is checked to be the Instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+/// Scratch account holding the balances recorded by `snapshot`. It is created by
+/// `snapshot` and closed by `verify_delta`, so the rent is refunded within the transaction.
+///
+/// Both instructions must be invoked directly by the transaction. Otherwise a program
+/// invoked in between with the authority's signature could close the snapshot through a
+/// CPI to `verify_delta`, then take a new one of the drained balances.
+#[account]
+pub struct BalanceSnapshot {
+    pub authority: Pubkey,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+/// At most this many accounts can be snapshotted, since the snapshot account is created
+/// through a CPI which can allocate at most `MAX_PERMITTED_DATA_INCREASE` bytes
+pub const MAX_SNAPSHOT_ACCOUNTS: usize =
+    (MAX_PERMITTED_DATA_INCREASE - BalanceSnapshot::BASE_SPACE) / SnapshotEntry::SIZE;
+
+impl BalanceSnapshot {
+    const BASE_SPACE: usize = 8 + 32 + 4;
+
+    /// The size of a snapshot of `num_accounts` accounts
+    pub fn space(num_accounts: usize) -> Result<usize> {
+        require_gte!(
+            MAX_SNAPSHOT_ACCOUNTS,
+            num_accounts,
+            ZipperError::TooManySnapshotAccounts
+        );
+        Ok(Self::BASE_SPACE + num_accounts * SnapshotEntry::SIZE)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SnapshotEntry {
+    pub key: Pubkey,
+    pub balance: u64,
+}
+
+impl SnapshotEntry {
+    pub const SIZE: usize = 32 + 8;
+}
+
 pub struct AccountZipper;
 
 impl AccountZipper {
@@ -84,6 +345,12 @@ impl AccountZipper {
     /// -->
     ///     assert!(balance(account1) >= balance1)
     ///     assert!(balance(account2) >= balance2)
+    ///
+    /// The comparison used for each account can be changed by passing `comparisons`
+    /// of the same length, e.g. `[Comparison::AtLeast, Comparison::Within { max: balance2 + 10 }]`,
+    /// and further assertions on the accounts can be added with `checks`, e.g.
+    /// `AccountCheck { index: 1, check: Check::Mint(usdc_mint) }`, or bounds on the summed
+    /// balances of several accounts with `groups`
     pub fn zip_accounts(keys: &[Pubkey]) -> Vec<AccountMeta> {
         keys.into_iter()
             .map(|&pubkey| AccountMeta {
@@ -93,6 +360,94 @@ impl AccountZipper {
             })
             .collect()
     }
+
+    /// The address of the snapshot account used by `snapshot` and `verify_delta` for `authority`
+    pub fn snapshot_address(authority: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[SNAPSHOT_SEED, authority.as_ref()], &ID).0
+    }
+}
+
+/// How the actual balance of an account is compared against its expected balance.
+///
+/// `AtLeast` costs a single byte per account; `Within` carries the upper bound of the
+/// range while the lower bound is the entry in `balances`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Comparison {
+    /// actual >= expected
+    #[default]
+    AtLeast,
+    /// actual <= expected
+    AtMost,
+    /// actual == expected
+    Exact,
+    /// expected <= actual <= max
+    Within { max: u64 },
+    /// No balance assertion. The account may be owned by any program and is
+    /// only subject to the `checks` that refer to it.
+    Any,
+}
+
+impl Comparison {
+    pub fn check(&self, actual: u64, expected: u64) -> Result<()> {
+        match *self {
+            Comparison::AtLeast => {
+                require_gte!(actual, expected, ZipperError::InsufficientBalance)
+            }
+            Comparison::AtMost => {
+                require_gte!(expected, actual, ZipperError::ExcessiveBalance)
+            }
+            Comparison::Exact => {
+                require_eq!(actual, expected, ZipperError::BalanceMismatch)
+            }
+            Comparison::Within { max } => {
+                if actual < expected || actual > max {
+                    return Err(error!(ZipperError::BalanceOutOfRange)
+                        .with_values((actual, format!("[{}, {}]", expected, max))));
+                }
+            }
+            Comparison::Any => {}
+        }
+        Ok(())
+    }
+
+    /// Same as `check`, but reports the failing account at `index` in a machine-parseable
+    /// form before returning the error for this comparison
+    pub fn check_account(&self, index: usize, actual: u64, expected: u64) -> Result<()> {
+        self.check(actual, expected).map_err(|error| {
+            msg!("balance check failed for account index {}", index);
+            emit!(BalanceCheckFailed {
+                index: index as u8,
+                actual,
+                expected,
+                comparison: *self,
+            });
+            error
+        })
+    }
+
+    pub(crate) fn describe(&self, expected: u64) -> String {
+        match *self {
+            Comparison::AtLeast => format!(">= {}", expected),
+            Comparison::AtMost => format!("<= {}", expected),
+            Comparison::Exact => format!("== {}", expected),
+            Comparison::Within { max } => format!("in [{}, {}]", expected, max),
+            Comparison::Any => "(any)".to_string(),
+        }
+    }
+}
+
+/// Emitted by `verify` when an account fails its comparison, right before the
+/// corresponding `ZipperError` is returned.
+///
+/// `expected` is the entry in `balances`; for `Comparison::Within` the upper bound
+/// is carried by `comparison`.
+#[event]
+pub struct BalanceCheckFailed {
+    /// Index of the failing account in the remaining accounts
+    pub index: u8,
+    pub actual: u64,
+    pub expected: u64,
+    pub comparison: Comparison,
 }
 
 #[error_code]
@@ -103,4 +458,102 @@ pub enum ZipperError {
     InsufficientBalance,
     #[msg("an account that is not an spl account was provided as an additional account")]
     NonSOLOrSPLAccountProvided,
+    #[msg("number of comparisons must be zero or match the number of expected_balances provided")]
+    InvalidNumberOfComparisons,
+    #[msg("one of the accounts has a higher-than-expected balance")]
+    ExcessiveBalance,
+    #[msg("one of the accounts does not have exactly the expected balance")]
+    BalanceMismatch,
+    #[msg("one of the accounts has a balance outside of the expected range")]
+    BalanceOutOfRange,
+    #[msg("the accounts provided do not match the accounts in the snapshot")]
+    SnapshotAccountMismatch,
+    #[msg("one of the accounts changed by less than the expected delta")]
+    InsufficientDelta,
+    #[msg("the instruction must be invoked directly by the transaction, not through a CPI")]
+    NotTopLevelInstruction,
+    #[msg("too many accounts to snapshot")]
+    TooManySnapshotAccounts,
+    #[msg("a check refers to an account index that was not provided")]
+    InvalidCheckIndex,
+    #[msg("a token account check was requested for an account that is not a token account")]
+    NotATokenAccount,
+    #[msg("one of the token accounts does not hold the expected mint")]
+    MintMismatch,
+    #[msg("one of the token accounts is not owned by the expected wallet")]
+    OwnerMismatch,
+    #[msg("one of the token accounts has an unexpected delegate")]
+    UnexpectedDelegate,
+    #[msg("one of the token accounts has a larger-than-expected delegated amount")]
+    ExcessiveDelegatedAmount,
+    #[msg("one of the token accounts has an unexpected close authority")]
+    UnexpectedCloseAuthority,
+    #[msg("one of the token accounts is frozen")]
+    AccountFrozen,
+    #[msg("one of the accounts is not owned by the expected program")]
+    ProgramOwnerMismatch,
+    #[msg("one of the accounts does not have the expected data length")]
+    DataLenMismatch,
+    #[msg("a data check refers to bytes outside of the account data")]
+    DataRangeOutOfBounds,
+    #[msg("one of the accounts does not have the expected data")]
+    DataHashMismatch,
+    #[msg("a balance group refers to the same account more than once")]
+    DuplicateGroupIndex,
+    #[msg("the packed instruction data is malformed")]
+    InvalidPackedData,
+    #[msg("the account provided is not the instructions sysvar")]
+    InvalidInstructionsSysvar,
+    #[msg("the transaction invokes a program that is not in the allowlist")]
+    ProgramNotAllowed,
+    #[msg("the zipper instruction is followed by other instructions")]
+    NotLastInstruction,
+    #[msg("the transaction executed after the slot deadline")]
+    SlotDeadlineExceeded,
+    #[msg("the transaction executed after the unix timestamp deadline")]
+    TimestampDeadlineExceeded,
+    #[msg("the transaction executed before the minimum slot")]
+    SlotTooEarly,
+    #[msg("one of the price accounts is not a valid price account of the oracle program")]
+    InvalidPriceAccount,
+    #[msg("one of the oracle prices is not currently trading")]
+    OraclePriceUnavailable,
+    #[msg("one of the oracle prices is stale")]
+    StaleOraclePrice,
+    #[msg("one of the oracle prices has a confidence interval that is too wide")]
+    OracleConfidenceTooWide,
+    #[msg("the portfolio value overflowed")]
+    ValueOverflow,
+    #[msg("the portfolio has a lower-than-expected value")]
+    InsufficientValue,
+    #[msg("one of the pool reserves is empty")]
+    EmptyPoolReserve,
+    #[msg("the pool price is outside of the expected range")]
+    PoolPriceOutOfRange,
+    #[msg("a mint check was requested for an account that is not a mint")]
+    NotAMintAccount,
+    #[msg("one of the mints does not have the expected decimals")]
+    DecimalsMismatch,
+    #[msg("one of the mints does not have the expected mint authority")]
+    MintAuthorityMismatch,
+    #[msg("one of the mints does not have the expected freeze authority")]
+    FreezeAuthorityMismatch,
+    #[msg("one of the stake accounts is not initialized")]
+    InvalidStakeAccount,
+    #[msg("a stake check was requested for an account that is not a stake account")]
+    NotAStakeAccount,
+    #[msg("one of the stake accounts is not delegated to the expected vote account")]
+    VoterMismatch,
+    #[msg("one of the stake accounts does not have the expected staker authority")]
+    StakerMismatch,
+    #[msg("one of the stake accounts does not have the expected withdrawer authority")]
+    WithdrawerMismatch,
+    #[msg("one of the stake accounts does not have the expected lockup")]
+    LockupMismatch,
+    #[msg("one of the token accounts does not hold exactly one token of an NFT mint")]
+    NotAnNft,
+    #[msg("one of the accounts is not an initialized nonce account")]
+    InvalidNonceAccount,
+    #[msg("one of the nonce accounts does not have the expected authority")]
+    NonceAuthorityMismatch,
 }
@@ -0,0 +1,32 @@
+//! A reader for durable nonce accounts.
+//!
+//! Nonce accounts are system program accounts holding a bincode-serialized
+//! `nonce::state::Versions`. Both the legacy and current versions share the layout of
+//! their `State`, which is read at its fixed offsets.
+
+use anchor_lang::prelude::*;
+
+use crate::ZipperError;
+
+const STATE_OFFSET: usize = 4;
+const STATE_INITIALIZED: u32 = 1;
+const AUTHORITY_OFFSET: usize = 8;
+
+/// The size of a nonce account
+pub const NONCE_ACCOUNT_LEN: usize = 80;
+
+/// The authority of an initialized nonce account
+pub fn authority(data: &[u8]) -> Result<Pubkey> {
+    require!(
+        data.len() == NONCE_ACCOUNT_LEN
+            && u32::from_le_bytes(data[0..4].try_into().unwrap()) <= 1
+            && u32::from_le_bytes(data[STATE_OFFSET..STATE_OFFSET + 4].try_into().unwrap())
+                == STATE_INITIALIZED,
+        ZipperError::InvalidNonceAccount
+    );
+    Ok(Pubkey::new_from_array(
+        data[AUTHORITY_OFFSET..AUTHORITY_OFFSET + 32]
+            .try_into()
+            .unwrap(),
+    ))
+}
@@ -0,0 +1,160 @@
+//! A minimal reader for Pyth-style (v2) price accounts, used to value balances.
+//!
+//! Only the fields needed to value a balance are read, at their offsets in the
+//! Pyth v2 `PriceAccount` layout, so any oracle program producing this layout can be used.
+
+use anchor_lang::prelude::*;
+
+use crate::{balance::extract_balance, ZipperError};
+
+/// Magic number at the start of every Pyth account
+pub const MAGIC: u32 = 0xa1b2c3d4;
+/// The supported version of the layout
+pub const VERSION: u32 = 2;
+/// Account type of price accounts
+pub const ACCOUNT_TYPE_PRICE: u32 = 3;
+/// Price status of a price that is currently updating as expected
+pub const STATUS_TRADING: u32 = 1;
+
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPO_OFFSET: usize = 20;
+const AGG_PRICE_OFFSET: usize = 208;
+const AGG_CONF_OFFSET: usize = 216;
+const AGG_STATUS_OFFSET: usize = 224;
+const AGG_PUB_SLOT_OFFSET: usize = 232;
+
+/// The smallest price account containing all of the fields read by `PriceFeed::parse`
+pub const MIN_PRICE_ACCOUNT_LEN: usize = AGG_PUB_SLOT_OFFSET + 8;
+
+/// The aggregate price of a price account, worth `price * 10^expo` per whole token
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceFeed {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub status: u32,
+    pub pub_slot: u64,
+}
+
+impl PriceFeed {
+    pub fn parse(data: &[u8]) -> Result<PriceFeed> {
+        require!(
+            data.len() >= MIN_PRICE_ACCOUNT_LEN
+                && read_u32(data, MAGIC_OFFSET) == MAGIC
+                && read_u32(data, VERSION_OFFSET) == VERSION
+                && read_u32(data, ACCOUNT_TYPE_OFFSET) == ACCOUNT_TYPE_PRICE,
+            ZipperError::InvalidPriceAccount
+        );
+        Ok(PriceFeed {
+            price: read_u64(data, AGG_PRICE_OFFSET) as i64,
+            conf: read_u64(data, AGG_CONF_OFFSET),
+            expo: read_u32(data, EXPO_OFFSET) as i32,
+            status: read_u32(data, AGG_STATUS_OFFSET),
+            pub_slot: read_u64(data, AGG_PUB_SLOT_OFFSET),
+        })
+    }
+
+    /// Checks that the price is positive and trading, was published at most
+    /// `max_staleness` slots before `slot`, and that its confidence interval is at
+    /// most `max_confidence_bps` basis points of the price.
+    pub fn validate(&self, slot: u64, max_staleness: u64, max_confidence_bps: u16) -> Result<()> {
+        require!(
+            self.status == STATUS_TRADING && self.price > 0,
+            ZipperError::OraclePriceUnavailable
+        );
+        require_gte!(
+            max_staleness,
+            slot.saturating_sub(self.pub_slot),
+            ZipperError::StaleOraclePrice
+        );
+        require_gte!(
+            max_confidence_bps as u128 * self.price as u128,
+            self.conf as u128 * 10_000,
+            ZipperError::OracleConfidenceTooWide
+        );
+        Ok(())
+    }
+
+    /// The value of `amount` base units of a token with `decimals` decimals, in units of
+    /// `10^value_expo`, rounded down.
+    ///
+    /// The lower end of the confidence interval, `price - conf`, is used so that the
+    /// value is never overestimated.
+    pub fn value(&self, amount: u64, decimals: u8, value_expo: i32) -> Result<u128> {
+        let price = (self.price as i128 - self.conf as i128).max(0) as u128;
+        let value = amount as u128 * price;
+        let expo = self.expo as i64 - decimals as i64 - value_expo as i64;
+        let scale = u32::try_from(expo.unsigned_abs())
+            .ok()
+            .and_then(|expo| 10_u128.checked_pow(expo));
+        let value = if expo >= 0 {
+            scale.and_then(|scale| value.checked_mul(scale))
+        } else {
+            // Anything scaled down by more than u128::MAX rounds down to zero
+            Some(scale.map(|scale| value / scale).unwrap_or(0))
+        };
+        value.ok_or_else(|| ZipperError::ValueOverflow.into())
+    }
+}
+
+/// Checks that the total value of the pairs of a SOL or SPL token account and the price
+/// account of its asset in `accs` is at least `min_value`, in units of `10^value_expo`,
+/// at `slot`. See `verify_value`.
+#[allow(clippy::too_many_arguments)]
+pub fn check_value(
+    accs: &[AccountInfo],
+    slot: u64,
+    min_value: u64,
+    value_expo: i32,
+    decimals: &[u8],
+    oracle_program: &Pubkey,
+    max_staleness: u64,
+    max_confidence_bps: u16,
+) -> Result<()> {
+    // Check that the number of accounts provided is correct
+    require_eq!(
+        accs.len(),
+        2 * decimals.len(),
+        ZipperError::InvalidNumberOfAccountsOrBalances
+    );
+
+    let mut total: u128 = 0;
+    for (pair, &decimals) in accs.chunks_exact(2).zip(decimals) {
+        let (acc, price_acc) = (&pair[0], &pair[1]);
+        let (balance, description) = extract_balance(acc)?;
+
+        require_keys_eq!(
+            *price_acc.owner,
+            *oracle_program,
+            ZipperError::InvalidPriceAccount
+        );
+        let price = PriceFeed::parse(&price_acc.data.borrow())?;
+        price.validate(slot, max_staleness, max_confidence_bps)?;
+
+        let value = price.value(balance, decimals, value_expo)?;
+        msg!(
+            "valued {} at {} (price {} ± {} x 10^{}) for {}",
+            balance,
+            value,
+            price.price,
+            price.conf,
+            price.expo,
+            description
+        );
+        total = total.checked_add(value).ok_or(ZipperError::ValueOverflow)?;
+    }
+
+    msg!("expected total value {} >= {}", total, min_value);
+    require_gte!(total, min_value as u128, ZipperError::InsufficientValue);
+    Ok(())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
@@ -0,0 +1,188 @@
+//! A compact, zero-copy encoding of `verify_with`'s balances and comparisons.
+//!
+//! The Borsh-encoded `verify_with` instruction is convenient but deserializes its arguments
+//! into heap-allocated vectors. This encoding is parsed in place from the instruction
+//! data, and only logs every account when asked to. All of the instruction's accounts
+//! are zipped accounts, in order. The instruction data is laid out as
+//!
+//! | bytes               | field                                                       |
+//! |---------------------|-------------------------------------------------------------|
+//! | 8                   | `PACKED_VERIFY_TAG`                                         |
+//! | 1                   | header flags, `FLAG_LOG` enables `msg!` logging             |
+//!
+//! followed by one entry per account
+//!
+//! | bytes               | field                                                       |
+//! |---------------------|-------------------------------------------------------------|
+//! | 1                   | comparison in the low 3 bits (`COMPARISON_*`), `FLAG_VARINT` |
+//! | 8 or varint         | expected balance, absent for `COMPARISON_ANY`               |
+//! | 8 or varint         | max balance, only for `COMPARISON_WITHIN`                   |
+//!
+//! Bounds are little endian `u64`s, or LEB128 varints if `FLAG_VARINT` is set. Any other
+//! header or entry flag bits must be zero, so that they can be given a meaning later.
+
+use anchor_lang::{prelude::*, solana_program::instruction::Instruction};
+
+use crate::{balance::ZippedAccount, AccountZipper, Comparison, ZipperError, ID};
+
+/// Instruction data prefix routing an instruction to the packed verify. Anchor
+/// dispatches any data not starting with an instruction discriminator to `fallback`.
+pub const PACKED_VERIFY_TAG: [u8; 8] = *b"zipverif";
+
+/// Header flag enabling per-account `msg!` logging
+pub const FLAG_LOG: u8 = 1 << 0;
+
+/// Entry flag marking the bounds of the entry as LEB128 varints
+pub const FLAG_VARINT: u8 = 1 << 3;
+const COMPARISON_MASK: u8 = 0b111;
+const ENTRY_FLAGS_MASK: u8 = COMPARISON_MASK | FLAG_VARINT;
+
+pub const COMPARISON_AT_LEAST: u8 = 0;
+pub const COMPARISON_AT_MOST: u8 = 1;
+pub const COMPARISON_EXACT: u8 = 2;
+pub const COMPARISON_WITHIN: u8 = 3;
+pub const COMPARISON_ANY: u8 = 4;
+
+/// Checks the balances of `accounts` against the packed entries in `data`, which
+/// excludes the `PACKED_VERIFY_TAG`.
+pub fn verify(accounts: &[AccountInfo], data: &[u8]) -> Result<()> {
+    let (&header, mut data) = data.split_first().ok_or(ZipperError::InvalidPackedData)?;
+    require!(header & !FLAG_LOG == 0, ZipperError::InvalidPackedData);
+    let log = header & FLAG_LOG != 0;
+
+    for (i, acc) in accounts.iter().enumerate() {
+        let (comparison, expected) = read_entry(&mut data)?;
+        if comparison == Comparison::Any {
+            continue;
+        }
+
+        let account = ZippedAccount::load(acc)?;
+        let actual = account.balance();
+        if log {
+            msg!(
+                "expected {} {} for {}",
+                actual,
+                comparison.describe(expected),
+                account.describe(acc.key),
+            );
+        }
+        comparison.check_account(i, actual, expected)?;
+    }
+
+    // Every entry must correspond to an account
+    require!(
+        data.is_empty(),
+        ZipperError::InvalidNumberOfAccountsOrBalances
+    );
+    Ok(())
+}
+
+fn read_entry(data: &mut &[u8]) -> Result<(Comparison, u64)> {
+    let bytes: &[u8] = data;
+    let (&flags, rest) = bytes
+        .split_first()
+        .ok_or(ZipperError::InvalidNumberOfAccountsOrBalances)?;
+    *data = rest;
+    require!(
+        flags & !ENTRY_FLAGS_MASK == 0,
+        ZipperError::InvalidPackedData
+    );
+
+    let varint = flags & FLAG_VARINT != 0;
+    let comparison = match flags & COMPARISON_MASK {
+        COMPARISON_AT_LEAST => Comparison::AtLeast,
+        COMPARISON_AT_MOST => Comparison::AtMost,
+        COMPARISON_EXACT => Comparison::Exact,
+        COMPARISON_WITHIN => {
+            let expected = read_bound(data, varint)?;
+            let max = read_bound(data, varint)?;
+            return Ok((Comparison::Within { max }, expected));
+        }
+        COMPARISON_ANY => return Ok((Comparison::Any, 0)),
+        _ => return Err(ZipperError::InvalidPackedData.into()),
+    };
+    Ok((comparison, read_bound(data, varint)?))
+}
+
+fn read_bound(data: &mut &[u8], varint: bool) -> Result<u64> {
+    let bytes: &[u8] = data;
+    if varint {
+        let mut value: u64 = 0;
+        for (i, &byte) in bytes.iter().enumerate().take(10) {
+            value |= ((byte & 0x7f) as u64)
+                .checked_shl(7 * i as u32)
+                .filter(|shifted| shifted >> (7 * i) == (byte & 0x7f) as u64)
+                .ok_or(ZipperError::InvalidPackedData)?;
+            if byte & 0x80 == 0 {
+                *data = &bytes[i + 1..];
+                return Ok(value);
+            }
+        }
+        Err(ZipperError::InvalidPackedData.into())
+    } else {
+        require!(bytes.len() >= 8, ZipperError::InvalidPackedData);
+        let (bound, rest) = bytes.split_at(8);
+        *data = rest;
+        Ok(u64::from_le_bytes(bound.try_into().unwrap()))
+    }
+}
+
+/// Encodes the packed instruction data for `balances` and `comparisons`, which must have
+/// the same length. Each entry uses varint bounds when they are shorter than 8 bytes.
+pub fn encode(balances: &[u64], comparisons: &[Comparison], log: bool) -> Vec<u8> {
+    assert_eq!(balances.len(), comparisons.len());
+    let mut data = PACKED_VERIFY_TAG.to_vec();
+    data.push(if log { FLAG_LOG } else { 0 });
+
+    for (&expected, comparison) in balances.iter().zip(comparisons) {
+        let (kind, bounds): (u8, &[u64]) = match *comparison {
+            Comparison::AtLeast => (COMPARISON_AT_LEAST, &[expected]),
+            Comparison::AtMost => (COMPARISON_AT_MOST, &[expected]),
+            Comparison::Exact => (COMPARISON_EXACT, &[expected]),
+            Comparison::Within { max } => (COMPARISON_WITHIN, &[expected, max]),
+            Comparison::Any => (COMPARISON_ANY, &[]),
+        };
+        let varint =
+            bounds.iter().map(|&bound| varint_len(bound)).sum::<usize>() < 8 * bounds.len();
+        data.push(if varint { kind | FLAG_VARINT } else { kind });
+        for &bound in bounds {
+            if varint {
+                write_varint(&mut data, bound);
+            } else {
+                data.extend_from_slice(&bound.to_le_bytes());
+            }
+        }
+    }
+    data
+}
+
+/// Builds a packed verify instruction checking `keys` against `balances` and `comparisons`
+pub fn instruction(
+    keys: &[Pubkey],
+    balances: &[u64],
+    comparisons: &[Comparison],
+    log: bool,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: AccountZipper::zip_accounts(keys),
+        data: encode(balances, comparisons, log),
+    }
+}
+
+fn varint_len(mut value: u64) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        data.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
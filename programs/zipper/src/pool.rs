@@ -0,0 +1,18 @@
+//! Prices of constant-product style pools, derived from their two token reserves.
+//!
+//! Prices are Q64.64 fixed point numbers: the amount of base units of the second reserve's
+//! token per base unit of the first reserve's token, multiplied by `2^64`.
+
+/// Number of fractional bits of a pool price
+pub const PRICE_FRACTIONAL_BITS: u32 = 64;
+
+/// The price of `reserve_a` in terms of `reserve_b`, or `None` if `reserve_a` is empty
+pub fn price(reserve_a: u64, reserve_b: u64) -> Option<u128> {
+    ((reserve_b as u128) << PRICE_FRACTIONAL_BITS).checked_div(reserve_a as u128)
+}
+
+/// Converts a price in base units of token b per base unit of token a to a Q64.64
+/// pool price, e.g. for building the band of `verify_pool_price` off-chain.
+pub fn price_from_f64(price: f64) -> u128 {
+    (price * (1_u128 << PRICE_FRACTIONAL_BITS) as f64) as u128
+}
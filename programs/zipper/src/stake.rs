@@ -0,0 +1,111 @@
+//! A reader for stake program accounts.
+//!
+//! Stake accounts are bincode-serialized `StakeState`s, which are read at their fixed
+//! offsets rather than deserialized in full.
+
+use anchor_lang::prelude::*;
+
+use crate::ZipperError;
+
+const STATE_INITIALIZED: u32 = 1;
+const STATE_STAKE: u32 = 2;
+
+const STAKER_OFFSET: usize = 12;
+const WITHDRAWER_OFFSET: usize = 44;
+const LOCKUP_UNIX_TIMESTAMP_OFFSET: usize = 76;
+const LOCKUP_EPOCH_OFFSET: usize = 84;
+const LOCKUP_CUSTODIAN_OFFSET: usize = 92;
+const VOTER_OFFSET: usize = 124;
+const STAKE_OFFSET: usize = 156;
+const DEACTIVATION_EPOCH_OFFSET: usize = 172;
+
+/// The size of a stake account
+pub const STAKE_ACCOUNT_LEN: usize = 200;
+
+/// An initialized stake account, optionally delegated
+pub struct StakeAccount {
+    pub staker: Pubkey,
+    pub withdrawer: Pubkey,
+    pub lockup: StakeLockup,
+    pub delegation: Option<StakeDelegation>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StakeLockup {
+    pub unix_timestamp: i64,
+    pub epoch: u64,
+    pub custodian: Pubkey,
+}
+
+pub struct StakeDelegation {
+    pub voter: Pubkey,
+    pub stake: u64,
+    /// The epoch in which the stake was deactivated, `u64::MAX` if it was not
+    pub deactivation_epoch: u64,
+}
+
+impl StakeDelegation {
+    /// Whether the stake has not been deactivated. Deactivating stake cools down and can
+    /// then be withdrawn, so it no longer counts as delegated.
+    pub fn is_active(&self) -> bool {
+        self.deactivation_epoch == u64::MAX
+    }
+}
+
+impl StakeAccount {
+    /// Reads an initialized or delegated stake account. Uninitialized stake accounts and
+    /// the rewards pool are rejected.
+    pub fn parse(data: &[u8]) -> Result<StakeAccount> {
+        require!(
+            data.len() >= STAKE_ACCOUNT_LEN,
+            ZipperError::InvalidStakeAccount
+        );
+        let delegation = match read_u32(data, 0) {
+            STATE_INITIALIZED => None,
+            STATE_STAKE => Some(StakeDelegation {
+                voter: read_pubkey(data, VOTER_OFFSET),
+                stake: read_u64(data, STAKE_OFFSET),
+                deactivation_epoch: read_u64(data, DEACTIVATION_EPOCH_OFFSET),
+            }),
+            _ => return Err(ZipperError::InvalidStakeAccount.into()),
+        };
+        Ok(StakeAccount {
+            staker: read_pubkey(data, STAKER_OFFSET),
+            withdrawer: read_pubkey(data, WITHDRAWER_OFFSET),
+            lockup: StakeLockup {
+                unix_timestamp: read_u64(data, LOCKUP_UNIX_TIMESTAMP_OFFSET) as i64,
+                epoch: read_u64(data, LOCKUP_EPOCH_OFFSET),
+                custodian: read_pubkey(data, LOCKUP_CUSTODIAN_OFFSET),
+            },
+            delegation,
+        })
+    }
+
+    /// The delegated stake, zero if the account is not delegated or its stake has been
+    /// deactivated
+    pub fn delegated_stake(&self) -> u64 {
+        self.active_delegation()
+            .map(|delegation| delegation.stake)
+            .unwrap_or(0)
+    }
+
+    /// The delegation, unless the account is not delegated or its stake has been
+    /// deactivated
+    pub fn active_delegation(&self) -> Option<&StakeDelegation> {
+        self.delegation
+            .as_ref()
+            .filter(|delegation| delegation.is_active())
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap())
+}
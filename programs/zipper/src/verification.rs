@@ -0,0 +1,132 @@
+use anchor_lang::{prelude::*, solana_program::program::set_return_data};
+
+use crate::{
+    balance::ZippedAccount,
+    checks::{AccountCheck, BalanceGroup},
+    Comparison, ZipperError,
+};
+
+/// At most this many balances fit in the 1024 bytes of return data of `verify`
+pub const MAX_RETURNED_BALANCES: usize = 128;
+
+/// Checks the balances of the zipped accounts `accs` against `balances` and
+/// `comparisons`, where an empty `comparisons` checks every account with
+/// `Comparison::AtLeast`, then runs the additional `checks` and `groups`.
+///
+/// The actual balances are set as the return data, see `return_data`. In a `dry_run`,
+/// every account is accepted and failed checks are only logged, including checks and
+/// groups referring to an account index that is out of range. Only a mismatched number
+/// of accounts, balances or comparisons fails a dry run.
+///
+/// Like the packed verify without `packed::FLAG_LOG`, only failures are logged, since
+/// formatting every account is a large part of the compute units consumed. A dry run,
+/// which is only simulated, also logs every account and check.
+pub fn verify_accounts(
+    accs: &[AccountInfo],
+    balances: &[u64],
+    comparisons: &[Comparison],
+    checks: &[AccountCheck],
+    groups: &[BalanceGroup],
+    dry_run: bool,
+) -> Result<()> {
+    // Check that the number of accounts provided is correct
+    require_eq!(
+        accs.len(),
+        balances.len(),
+        ZipperError::InvalidNumberOfAccountsOrBalances
+    );
+
+    // An empty comparisons vec means every account is checked with `Comparison::AtLeast`
+    if !comparisons.is_empty() {
+        require_eq!(
+            comparisons.len(),
+            balances.len(),
+            ZipperError::InvalidNumberOfComparisons
+        );
+    }
+
+    // Check that all accounts provided are either token accounts or system
+    // program accounts (unless their balance is not checked) and extract balances
+    let accounts: Vec<ZippedAccount> = accs
+        .iter()
+        .enumerate()
+        .map(|(i, acc)| match comparisons.get(i) {
+            // Accounts without a balance check may be owned by any program, and a dry
+            // run reports the lamports of any account it cannot deserialize
+            Some(Comparison::Any) => Ok(ZippedAccount::load_any(acc)),
+            _ if dry_run => Ok(ZippedAccount::load_any(acc)),
+            _ => ZippedAccount::load(acc),
+        })
+        .collect::<Result<Vec<ZippedAccount>>>()?;
+
+    // Report the actual balances, so that simulations can read them directly
+    set_return_data(&return_data(accounts.iter().map(ZippedAccount::balance)));
+
+    // In a dry run, failed checks are logged instead of failing the instruction
+    let outcome = |result: Result<()>| match result {
+        Err(error) if dry_run => {
+            msg!("dry run: {}", error);
+            Ok(())
+        }
+        result => result,
+    };
+
+    // Check Balances
+    for (i, (account, acc)) in accounts.iter().zip(accs).enumerate() {
+        let actual = account.balance();
+        let comparison = comparisons.get(i).copied().unwrap_or_default();
+        if dry_run {
+            msg!(
+                "expected {} {} for {}",
+                actual,
+                comparison.describe(balances[i]),
+                account.describe(acc.key),
+            );
+        }
+        outcome(comparison.check_account(i, actual, balances[i]))?;
+    }
+
+    // Run any additional checks
+    for &AccountCheck { index, check } in checks {
+        if dry_run {
+            msg!("checking {:?} for account index {}", check, index);
+        }
+        let result = if (index as usize) < accounts.len() {
+            check.verify(accs, &accounts, index as usize)
+        } else {
+            Err(error!(ZipperError::InvalidCheckIndex))
+        };
+        outcome(result.map_err(|error| {
+            msg!("{:?} failed for account index {}", check, index);
+            error
+        }))?;
+    }
+
+    // Check the summed balances of any groups of accounts
+    for group in groups {
+        outcome(group.verify(accs, &accounts))?;
+    }
+    Ok(())
+}
+
+/// Encodes the return data of `verify`: the actual balances as little endian `u64`s, in
+/// the order of the zipped accounts.
+///
+/// Only the balances of the first `MAX_RETURNED_BALANCES` accounts are returned; the
+/// accounts after them are still checked. The kind of each account is not recorded, so
+/// a balance is a token amount, a lamport balance, a mint supply or delegated stake
+/// depending on the account zipped at its index, see `ZippedAccount::balance`.
+pub fn return_data(balances: impl Iterator<Item = u64>) -> Vec<u8> {
+    balances
+        .take(MAX_RETURNED_BALANCES)
+        .flat_map(u64::to_le_bytes)
+        .collect()
+}
+
+/// Decodes the return data of `verify`, e.g. from a simulation, into the actual balances
+/// of the first `MAX_RETURNED_BALANCES` zipped accounts, in order.
+pub fn parse_return_data(data: &[u8]) -> Vec<u64> {
+    data.chunks_exact(8)
+        .map(|balance| u64::from_le_bytes(balance.try_into().unwrap()))
+        .collect()
+}
@@ -0,0 +1,83 @@
+use std::rc::Rc;
+
+use anchor_client::{
+    solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig},
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        signature::{read_keypair_file, Keypair},
+        signer::Signer,
+        system_instruction,
+        transaction::{Transaction, TransactionError},
+    },
+    Client, Cluster, Program,
+};
+use anchor_lang::prelude::Pubkey;
+use zipper::{client::ZipperBuilder, ID as PROGRAM_ID};
+
+const TRANSFER: u64 = 1_000_000;
+
+/// Builds a zipped transfer from simulated post-balances, then checks that it passes and
+/// that draining the payer in its place fails. Requires a local validator with zipper
+/// deployed.
+#[test]
+fn build_zipped_transfer() {
+    let dev_key: Keypair = read_keypair_file(&*shellexpand::tilde("../../dev_key.json"))
+        .expect("Example requires a keypair file");
+    let client: Client = Client::new_with_options(
+        Cluster::Localnet,
+        Rc::new(Keypair::from_bytes(dev_key.to_bytes().as_ref()).unwrap()),
+        CommitmentConfig::processed(),
+    );
+    let program: Program = client.program(PROGRAM_ID);
+    let solana_client: RpcClient = program.rpc();
+    let payer = dev_key.pubkey();
+    let recipient = Pubkey::new_unique();
+
+    let builder = ZipperBuilder::new(&solana_client, payer)
+        .instruction(system_instruction::transfer(&payer, &recipient, TRANSFER));
+    assert_eq!(builder.writable_accounts(), vec![payer, recipient]);
+
+    // The payer's post-balance already has the transfer and the transaction fee deducted
+    let post_balances = builder.simulate().unwrap();
+    assert_eq!(
+        post_balances
+            .iter()
+            .map(|post| (post.key, post.mint))
+            .collect::<Vec<_>>(),
+        vec![(payer, None), (recipient, None)]
+    );
+    assert_eq!(post_balances[1].balance, TRANSFER);
+    let balance = solana_client.get_balance(&payer).unwrap();
+    assert!(post_balances[0].balance < balance - TRANSFER);
+
+    let zipped = builder.build().unwrap();
+    assert_eq!(zipped.len(), 2);
+    assert_eq!(zipped[1].program_id, PROGRAM_ID);
+    assert_eq!(simulate(&solana_client, &payer, &zipped), None);
+
+    // Sending one more lamport than was simulated drains the payer below its bound
+    let mut drained = zipped;
+    drained[0] = system_instruction::transfer(&payer, &recipient, TRANSFER + 1);
+    assert!(simulate(&solana_client, &payer, &drained).is_some());
+}
+
+fn simulate(
+    solana_client: &RpcClient,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+) -> Option<TransactionError> {
+    let transaction = Transaction::new_with_payer(instructions, Some(payer));
+    solana_client
+        .simulate_transaction_with_config(
+            &transaction,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .expect("failed to simulate transaction")
+        .value
+        .err
+}
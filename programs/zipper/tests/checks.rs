@@ -0,0 +1,889 @@
+use anchor_lang::{
+    error::{AnchorError, ComparedValues},
+    prelude::*,
+    solana_program::{
+        nonce::state::{Data, State, Versions},
+        program_option::COption,
+        program_pack::Pack,
+        stake::{
+            self,
+            state::{Authorized, Delegation, Meta, Stake, StakeState},
+        },
+    },
+};
+use anchor_spl::token::spl_token::{
+    self,
+    state::{Account as SplAccount, AccountState, Mint as SplMint},
+};
+use spl_token_2022::extension::{
+    transfer_fee::TransferFeeAmount, ExtensionType, StateWithExtensionsMut,
+};
+use zipper::{
+    balance::{extract_balance, ZippedAccount},
+    stake::STAKE_ACCOUNT_LEN,
+    verification::{parse_return_data, return_data, verify_accounts, MAX_RETURNED_BALANCES},
+    AccountCheck, BalanceGroup, Check, Comparison, ZipperError,
+};
+
+const TOKEN_ACCOUNT_RENT: u64 = 2_039_280;
+
+/// An initialized token account of `mint` owned by `owner`
+fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> SplAccount {
+    SplAccount {
+        mint,
+        owner,
+        amount,
+        state: AccountState::Initialized,
+        ..SplAccount::default()
+    }
+}
+
+/// An initialized mint without authorities
+fn mint(supply: u64, decimals: u8) -> SplMint {
+    SplMint {
+        supply,
+        decimals,
+        is_initialized: true,
+        ..SplMint::default()
+    }
+}
+
+/// A stake account delegating `stake` lamports to `voter`
+fn delegated_stake(authority: Pubkey, voter: Pubkey, stake: u64) -> StakeState {
+    StakeState::Stake(
+        Meta {
+            authorized: Authorized::auto(&authority),
+            ..Meta::default()
+        },
+        Stake {
+            delegation: Delegation {
+                voter_pubkey: voter,
+                stake,
+                ..Delegation::default()
+            },
+            ..Stake::default()
+        },
+    )
+}
+
+/// An account held by the test, from which the `AccountInfo`s given to checks are borrowed
+struct TestAccount {
+    key: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+}
+
+impl TestAccount {
+    fn new(owner: Pubkey, lamports: u64, data: Vec<u8>) -> TestAccount {
+        TestAccount {
+            key: Pubkey::new_unique(),
+            lamports,
+            data,
+            owner,
+        }
+    }
+
+    fn sol(lamports: u64) -> TestAccount {
+        TestAccount::new(System::id(), lamports, vec![])
+    }
+
+    /// An SPL Token account
+    fn token(account: SplAccount) -> TestAccount {
+        let mut data = vec![0; SplAccount::LEN];
+        account.pack_into_slice(&mut data);
+        TestAccount::new(spl_token::ID, TOKEN_ACCOUNT_RENT, data)
+    }
+
+    /// A Token-2022 account with the transfer fee extension, withholding `withheld` tokens
+    fn token_2022(account: SplAccount, withheld: u64) -> TestAccount {
+        let mut data = vec![
+            0;
+            ExtensionType::get_account_len::<spl_token_2022::state::Account>(&[
+                ExtensionType::TransferFeeAmount
+            ])
+        ];
+        let mut state =
+            StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack_uninitialized(
+                &mut data,
+            )
+            .unwrap();
+        state
+            .init_extension::<TransferFeeAmount>()
+            .unwrap()
+            .withheld_amount = withheld.into();
+        state.init_account_type().unwrap();
+        // Both programs share the layout of the base account
+        account.pack_into_slice(&mut data[..SplAccount::LEN]);
+        TestAccount::new(spl_token_2022::ID, TOKEN_ACCOUNT_RENT, data)
+    }
+
+    /// An SPL Token mint
+    fn mint(mint: SplMint) -> TestAccount {
+        let mut data = vec![0; SplMint::LEN];
+        mint.pack_into_slice(&mut data);
+        TestAccount::new(spl_token::ID, 1_461_600, data)
+    }
+
+    /// A stake account in `state`, serialized as the stake program does
+    fn stake(state: StakeState) -> TestAccount {
+        let mut data = bincode::serialize(&state).unwrap();
+        data.resize(STAKE_ACCOUNT_LEN, 0);
+        TestAccount::new(stake::program::ID, 2_282_880, data)
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            false,
+            false,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+/// Runs `check` on the account at `index` of `accounts`
+fn run(check: Check, accounts: &mut [TestAccount], index: usize) -> Result<()> {
+    let accs: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+    let zipped: Vec<ZippedAccount> = accs.iter().map(ZippedAccount::load_any).collect();
+    check.verify(&accs, &zipped, index)
+}
+
+#[test]
+fn comparisons() {
+    // Bounds are inclusive
+    assert!(Comparison::AtLeast.check(10, 10).is_ok());
+    assert!(Comparison::AtLeast.check(11, 10).is_ok());
+    assert_eq!(
+        Comparison::AtLeast.check(9, 10).unwrap_err(),
+        ZipperError::InsufficientBalance.into()
+    );
+
+    assert!(Comparison::AtMost.check(10, 10).is_ok());
+    assert!(Comparison::AtMost.check(0, 10).is_ok());
+    assert_eq!(
+        Comparison::AtMost.check(11, 10).unwrap_err(),
+        ZipperError::ExcessiveBalance.into()
+    );
+
+    assert!(Comparison::Exact.check(10, 10).is_ok());
+    for actual in [9, 11] {
+        assert_eq!(
+            Comparison::Exact.check(actual, 10).unwrap_err(),
+            ZipperError::BalanceMismatch.into()
+        );
+    }
+
+    let within = Comparison::Within { max: 20 };
+    for actual in [10, 15, 20] {
+        assert!(within.check(actual, 10).is_ok());
+    }
+    for actual in [0, 9, 21, u64::MAX] {
+        assert_eq!(
+            within.check(actual, 10).unwrap_err(),
+            ZipperError::BalanceOutOfRange.into()
+        );
+    }
+    // An empty range never passes
+    assert!(Comparison::Within { max: 9 }.check(10, 10).is_err());
+
+    for actual in [0, 10, u64::MAX] {
+        assert!(Comparison::Any.check(actual, 10).is_ok());
+    }
+
+    // `verify` checks every account with `AtLeast`
+    assert_eq!(Comparison::default(), Comparison::AtLeast);
+}
+
+/// Runs `verify_with` on `accounts`
+fn verify(
+    accounts: &mut [TestAccount],
+    balances: &[u64],
+    comparisons: &[Comparison],
+    checks: &[AccountCheck],
+    groups: &[BalanceGroup],
+) -> Result<()> {
+    let accs: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+    verify_accounts(&accs, balances, comparisons, checks, groups, false)
+}
+
+#[test]
+fn insufficient_balance() {
+    let mut accounts = [TestAccount::sol(10), TestAccount::sol(9)];
+    assert!(verify(&mut accounts, &[10, 9], &[], &[], &[]).is_ok());
+
+    // The error carries the actual and expected balances of the failing account
+    let error = verify(&mut accounts, &[10, 10], &[], &[], &[]).unwrap_err();
+    assert_eq!(error, ZipperError::InsufficientBalance.into());
+    assert!(matches!(
+        error,
+        Error::AnchorError(AnchorError {
+            compared_values: Some(ComparedValues::Values((actual, expected))),
+            ..
+        }) if actual == "9" && expected == "10"
+    ));
+
+    // One balance and at most one comparison per account
+    assert_eq!(
+        verify(&mut accounts, &[10], &[], &[], &[]).unwrap_err(),
+        ZipperError::InvalidNumberOfAccountsOrBalances.into()
+    );
+    assert_eq!(
+        verify(&mut accounts, &[10, 9], &[Comparison::AtLeast], &[], &[]).unwrap_err(),
+        ZipperError::InvalidNumberOfComparisons.into()
+    );
+}
+
+/// The data of a durable nonce account in the given state, as the system program
+/// serializes it
+fn nonce_account(state: State) -> Vec<u8> {
+    bincode::serialize(&Versions::Current(Box::new(state))).unwrap()
+}
+
+#[test]
+fn nonce_authority() {
+    let authority = Pubkey::new_unique();
+    let data = nonce_account(State::Initialized(Data {
+        authority,
+        ..Data::default()
+    }));
+    let mut accounts = [TestAccount::new(System::id(), 1_447_680, data.clone())];
+    assert!(run(Check::NonceAuthority(authority), &mut accounts, 0).is_ok());
+
+    // Another authority
+    assert_eq!(
+        run(
+            Check::NonceAuthority(Pubkey::new_unique()),
+            &mut accounts,
+            0
+        )
+        .unwrap_err(),
+        ZipperError::NonceAuthorityMismatch.into()
+    );
+
+    // Uninitialized nonce accounts, accounts of another owner and other system accounts
+    let mut uninitialized = [TestAccount::new(
+        System::id(),
+        1_447_680,
+        nonce_account(State::Uninitialized),
+    )];
+    let mut not_system = [TestAccount::new(Pubkey::new_unique(), 1_447_680, data)];
+    let mut wallet = [TestAccount::new(System::id(), 1_000_000, vec![])];
+    for accounts in [&mut uninitialized, &mut not_system, &mut wallet] {
+        assert_eq!(
+            run(Check::NonceAuthority(authority), accounts, 0).unwrap_err(),
+            ZipperError::InvalidNonceAccount.into()
+        );
+    }
+}
+
+#[test]
+fn token_2022_accounts() {
+    let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut accounts = [
+        TestAccount::token(token_account(mint, owner, 100)),
+        TestAccount::token_2022(token_account(mint, owner, 100), 5),
+    ];
+    {
+        let accs: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+        let zipped: Vec<ZippedAccount> = accs
+            .iter()
+            .map(|acc| ZippedAccount::load(acc).unwrap())
+            .collect();
+        assert_eq!(zipped[0].token().unwrap().withheld, None);
+        assert_eq!(zipped[1].token().unwrap().withheld, Some(5));
+        assert!(zipped[1].describe(accs[1].key).starts_with("spl-2022 addr"));
+    }
+
+    // Withheld transfer fees are not part of the balance
+    assert!(verify(&mut accounts, &[100, 100], &[], &[], &[]).is_ok());
+    assert_eq!(
+        verify(&mut accounts, &[100, 101], &[], &[], &[]).unwrap_err(),
+        ZipperError::InsufficientBalance.into()
+    );
+
+    // Accounts of any other program are rejected
+    let mut other = [TestAccount::new(
+        Pubkey::new_unique(),
+        1,
+        vec![0; SplAccount::LEN],
+    )];
+    assert_eq!(
+        verify(&mut other, &[0], &[], &[], &[]).unwrap_err(),
+        ZipperError::NonSOLOrSPLAccountProvided.into()
+    );
+}
+
+#[test]
+fn mint_and_owner() {
+    let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut accounts = [
+        TestAccount::token(token_account(mint, owner, 100)),
+        TestAccount::sol(100),
+    ];
+    assert!(run(Check::Mint(mint), &mut accounts, 0).is_ok());
+    assert!(run(Check::Owner(owner), &mut accounts, 0).is_ok());
+    assert_eq!(
+        run(Check::Mint(Pubkey::new_unique()), &mut accounts, 0).unwrap_err(),
+        ZipperError::MintMismatch.into()
+    );
+    assert_eq!(
+        run(Check::Owner(Pubkey::new_unique()), &mut accounts, 0).unwrap_err(),
+        ZipperError::OwnerMismatch.into()
+    );
+
+    // Token checks on a SOL account
+    assert_eq!(
+        run(Check::Owner(owner), &mut accounts, 1).unwrap_err(),
+        ZipperError::NotATokenAccount.into()
+    );
+
+    // Checks must refer to one of the accounts
+    let checks = [AccountCheck {
+        index: 2,
+        check: Check::Mint(mint),
+    }];
+    assert_eq!(
+        verify(&mut accounts, &[0, 0], &[], &checks, &[]).unwrap_err(),
+        ZipperError::InvalidCheckIndex.into()
+    );
+}
+
+#[test]
+fn delegate_close_authority_and_freeze() {
+    let (mint, owner, delegate) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let plain = token_account(mint, owner, 100);
+    let mut accounts = [
+        TestAccount::token(plain),
+        TestAccount::token(SplAccount {
+            delegate: COption::Some(delegate),
+            delegated_amount: 10,
+            close_authority: COption::Some(delegate),
+            state: AccountState::Frozen,
+            ..plain
+        }),
+    ];
+
+    for check in [
+        Check::NoDelegate,
+        Check::DelegateAtMost {
+            delegate: Pubkey::new_unique(),
+            amount: 0,
+        },
+        Check::NoCloseAuthority,
+        Check::NotFrozen,
+    ] {
+        assert!(run(check, &mut accounts, 0).is_ok());
+    }
+
+    assert_eq!(
+        run(Check::NoDelegate, &mut accounts, 1).unwrap_err(),
+        ZipperError::UnexpectedDelegate.into()
+    );
+    assert!(run(
+        Check::DelegateAtMost {
+            delegate,
+            amount: 10
+        },
+        &mut accounts,
+        1
+    )
+    .is_ok());
+    assert_eq!(
+        run(
+            Check::DelegateAtMost {
+                delegate,
+                amount: 9
+            },
+            &mut accounts,
+            1
+        )
+        .unwrap_err(),
+        ZipperError::ExcessiveDelegatedAmount.into()
+    );
+    assert_eq!(
+        run(
+            Check::DelegateAtMost {
+                delegate: Pubkey::new_unique(),
+                amount: u64::MAX
+            },
+            &mut accounts,
+            1
+        )
+        .unwrap_err(),
+        ZipperError::UnexpectedDelegate.into()
+    );
+    assert_eq!(
+        run(Check::NoCloseAuthority, &mut accounts, 1).unwrap_err(),
+        ZipperError::UnexpectedCloseAuthority.into()
+    );
+    assert_eq!(
+        run(Check::NotFrozen, &mut accounts, 1).unwrap_err(),
+        ZipperError::AccountFrozen.into()
+    );
+}
+
+#[test]
+fn arbitrary_accounts() {
+    let program = Pubkey::new_unique();
+    let data: Vec<u8> = (0..64).collect();
+    let mut accounts = [TestAccount::new(program, 1_000, data.clone())];
+
+    // Only accepted without a balance check
+    assert_eq!(
+        verify(&mut accounts, &[0], &[], &[], &[]).unwrap_err(),
+        ZipperError::NonSOLOrSPLAccountProvided.into()
+    );
+    let checks = [
+        AccountCheck {
+            index: 0,
+            check: Check::ProgramOwner(program),
+        },
+        AccountCheck {
+            index: 0,
+            check: Check::DataLen(64),
+        },
+        AccountCheck {
+            index: 0,
+            check: Check::data_hash(&data, 8, 16),
+        },
+    ];
+    assert!(verify(&mut accounts, &[0], &[Comparison::Any], &checks, &[]).is_ok());
+
+    assert_eq!(
+        run(Check::ProgramOwner(System::id()), &mut accounts, 0).unwrap_err(),
+        ZipperError::ProgramOwnerMismatch.into()
+    );
+    assert_eq!(
+        run(Check::DataLen(65), &mut accounts, 0).unwrap_err(),
+        ZipperError::DataLenMismatch.into()
+    );
+
+    // Any change to the hashed bytes
+    let mut changed = data.clone();
+    changed[23] ^= 1;
+    assert_eq!(
+        run(Check::data_hash(&changed, 8, 16), &mut accounts, 0).unwrap_err(),
+        ZipperError::DataHashMismatch.into()
+    );
+    // An empty range, the whole data and ranges past its end
+    assert!(run(Check::data_hash(&data, 64, 0), &mut accounts, 0).is_ok());
+    assert!(run(Check::data_hash(&data, 0, 64), &mut accounts, 0).is_ok());
+    for (offset, len) in [(0, 65), (60, 5), (u32::MAX, 1), (1, u32::MAX)] {
+        let check = Check::DataHash {
+            offset,
+            len,
+            hash: [0; 32],
+        };
+        assert_eq!(
+            run(check, &mut accounts, 0).unwrap_err(),
+            ZipperError::DataRangeOutOfBounds.into()
+        );
+    }
+}
+
+#[test]
+fn lamports() {
+    let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut accounts = [
+        TestAccount::new(Pubkey::new_unique(), 5_000, vec![0; 8]),
+        TestAccount::token(token_account(mint, owner, 100)),
+    ];
+
+    // Lamports of an account of any program
+    let at_least = |amount| Check::Lamports {
+        comparison: Comparison::AtLeast,
+        amount,
+    };
+    assert!(run(at_least(5_000), &mut accounts, 0).is_ok());
+    assert_eq!(
+        run(at_least(5_001), &mut accounts, 0).unwrap_err(),
+        ZipperError::InsufficientBalance.into()
+    );
+    let within = Check::Lamports {
+        comparison: Comparison::Within { max: 4_999 },
+        amount: 0,
+    };
+    assert_eq!(
+        run(within, &mut accounts, 0).unwrap_err(),
+        ZipperError::BalanceOutOfRange.into()
+    );
+
+    // The rent lamports of a token account, alongside its token amount
+    let checks = [AccountCheck {
+        index: 1,
+        check: Check::Lamports {
+            comparison: Comparison::Exact,
+            amount: TOKEN_ACCOUNT_RENT,
+        },
+    }];
+    assert!(verify(
+        &mut accounts,
+        &[0, 100],
+        &[Comparison::Any, Comparison::Exact],
+        &checks,
+        &[]
+    )
+    .is_ok());
+    assert_eq!(
+        verify(
+            &mut accounts,
+            &[0, TOKEN_ACCOUNT_RENT],
+            &[Comparison::Any, Comparison::Exact],
+            &checks,
+            &[]
+        )
+        .unwrap_err(),
+        ZipperError::BalanceMismatch.into()
+    );
+}
+
+#[test]
+fn balance_groups() {
+    let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut accounts = [
+        TestAccount::sol(300),
+        TestAccount::token(token_account(mint, owner, 200)),
+        TestAccount::sol(u64::MAX),
+    ];
+    let group = |indices: Vec<u8>, comparison, amount| BalanceGroup {
+        indices,
+        comparison,
+        amount,
+    };
+
+    // Native SOL plus a wrapped SOL token account
+    let groups = [group(vec![0, 1], Comparison::AtLeast, 500)];
+    assert!(verify(&mut accounts, &[0, 0, 0], &[], &[], &groups).is_ok());
+    let groups = [group(vec![0, 1], Comparison::AtLeast, 501)];
+    assert_eq!(
+        verify(&mut accounts, &[0, 0, 0], &[], &[], &groups).unwrap_err(),
+        ZipperError::InsufficientBalance.into()
+    );
+    let groups = [group(vec![1, 0], Comparison::Within { max: 499 }, 0)];
+    assert_eq!(
+        verify(&mut accounts, &[0, 0, 0], &[], &[], &groups).unwrap_err(),
+        ZipperError::BalanceOutOfRange.into()
+    );
+
+    // Totals saturate instead of wrapping around
+    let groups = [group(vec![0, 2], Comparison::Exact, u64::MAX)];
+    assert!(verify(&mut accounts, &[0, 0, 0], &[], &[], &groups).is_ok());
+
+    // Groups must refer to accounts that were zipped, each at most once
+    let groups = [group(vec![0, 3], Comparison::AtLeast, 0)];
+    assert_eq!(
+        verify(&mut accounts, &[0, 0, 0], &[], &[], &groups).unwrap_err(),
+        ZipperError::InvalidCheckIndex.into()
+    );
+    let groups = [group(vec![0, 1, 0], Comparison::AtLeast, 0)];
+    assert_eq!(
+        verify(&mut accounts, &[0, 0, 0], &[], &[], &groups).unwrap_err(),
+        ZipperError::DuplicateGroupIndex.into()
+    );
+
+    // The same account zipped twice is still counted once
+    accounts[2].key = accounts[0].key;
+    accounts[2].lamports = 300;
+    let groups = [group(vec![0, 2], Comparison::AtLeast, 0)];
+    assert_eq!(
+        verify(&mut accounts, &[0, 0, 0], &[], &[], &groups).unwrap_err(),
+        ZipperError::DuplicateGroupIndex.into()
+    );
+}
+
+#[test]
+fn balance_extraction() {
+    let (owner, voter) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut accounts = [
+        TestAccount::sol(5_000),
+        TestAccount::token(token_account(Pubkey::new_unique(), owner, 100)),
+        TestAccount::mint(mint(1_000, 6)),
+        TestAccount::stake(delegated_stake(owner, voter, 1_000_000)),
+        TestAccount::new(Pubkey::new_unique(), 5_000, vec![]),
+    ];
+    let accs: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+
+    // Only SOL and token accounts hold a balance of their own
+    assert_eq!(extract_balance(&accs[0]).unwrap().0, 5_000);
+    assert_eq!(extract_balance(&accs[1]).unwrap().0, 100);
+    for acc in &accs[2..] {
+        assert_eq!(
+            extract_balance(acc).unwrap_err(),
+            ZipperError::NonSOLOrSPLAccountProvided.into()
+        );
+    }
+
+    // While `verify` compares the supply of mints and the delegated stake
+    let zipped: Vec<ZippedAccount> = accs.iter().map(ZippedAccount::load_any).collect();
+    let balances: Vec<u64> = zipped.iter().map(ZippedAccount::balance).collect();
+    assert_eq!(balances, [5_000, 100, 1_000, 1_000_000, 5_000]);
+}
+
+#[test]
+fn mint_checks() {
+    let authority = Pubkey::new_unique();
+    let mut accounts = [
+        TestAccount::mint(mint(1_000, 6)),
+        TestAccount::mint(SplMint {
+            mint_authority: COption::Some(authority),
+            freeze_authority: COption::Some(authority),
+            ..mint(1_000, 6)
+        }),
+        TestAccount::token(token_account(Pubkey::new_unique(), authority, 100)),
+    ];
+
+    for check in [
+        Check::Decimals(6),
+        Check::MintAuthority(None),
+        Check::FreezeAuthority(None),
+    ] {
+        assert!(run(check, &mut accounts, 0).is_ok());
+    }
+    assert!(run(Check::MintAuthority(Some(authority)), &mut accounts, 1).is_ok());
+    assert!(run(Check::FreezeAuthority(Some(authority)), &mut accounts, 1).is_ok());
+
+    assert_eq!(
+        run(Check::Decimals(9), &mut accounts, 0).unwrap_err(),
+        ZipperError::DecimalsMismatch.into()
+    );
+    assert_eq!(
+        run(Check::MintAuthority(Some(authority)), &mut accounts, 0).unwrap_err(),
+        ZipperError::MintAuthorityMismatch.into()
+    );
+    assert_eq!(
+        run(Check::FreezeAuthority(None), &mut accounts, 1).unwrap_err(),
+        ZipperError::FreezeAuthorityMismatch.into()
+    );
+
+    // Mint checks on a token account, and token checks on a mint
+    assert_eq!(
+        run(Check::Decimals(6), &mut accounts, 2).unwrap_err(),
+        ZipperError::NotAMintAccount.into()
+    );
+    assert_eq!(
+        run(Check::NotFrozen, &mut accounts, 0).unwrap_err(),
+        ZipperError::NotATokenAccount.into()
+    );
+
+    // The supply can be capped like a balance
+    let comparisons = [Comparison::AtMost, Comparison::Any, Comparison::Any];
+    assert!(verify(&mut accounts, &[1_000, 0, 0], &comparisons, &[], &[]).is_ok());
+    assert_eq!(
+        verify(&mut accounts, &[999, 0, 0], &comparisons, &[], &[]).unwrap_err(),
+        ZipperError::ExcessiveBalance.into()
+    );
+}
+
+#[test]
+fn stake_checks() {
+    let (authority, voter) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let active = delegated_stake(authority, voter, 1_000_000);
+    let deactivating = match active {
+        StakeState::Stake(meta, mut stake) => {
+            stake.delegation.deactivation_epoch = 10;
+            StakeState::Stake(meta, stake)
+        }
+        _ => unreachable!(),
+    };
+    let lockup = zipper::stake::StakeLockup {
+        unix_timestamp: 0,
+        epoch: 0,
+        custodian: Pubkey::default(),
+    };
+    let mut accounts = [
+        TestAccount::stake(active),
+        TestAccount::stake(deactivating),
+        TestAccount::stake(StakeState::Initialized(Meta::auto(&authority))),
+        TestAccount::stake(StakeState::Uninitialized),
+    ];
+
+    for check in [
+        Check::Voter(voter),
+        Check::Staker(authority),
+        Check::Withdrawer(authority),
+        Check::Lockup(lockup),
+    ] {
+        assert!(run(check, &mut accounts, 0).is_ok());
+    }
+    assert_eq!(
+        run(Check::Voter(Pubkey::new_unique()), &mut accounts, 0).unwrap_err(),
+        ZipperError::VoterMismatch.into()
+    );
+    assert_eq!(
+        run(Check::Staker(voter), &mut accounts, 0).unwrap_err(),
+        ZipperError::StakerMismatch.into()
+    );
+    assert_eq!(
+        run(Check::Withdrawer(voter), &mut accounts, 0).unwrap_err(),
+        ZipperError::WithdrawerMismatch.into()
+    );
+    let custodian = Pubkey::new_unique();
+    assert_eq!(
+        run(
+            Check::Lockup(zipper::stake::StakeLockup {
+                custodian,
+                ..lockup
+            }),
+            &mut accounts,
+            0
+        )
+        .unwrap_err(),
+        ZipperError::LockupMismatch.into()
+    );
+
+    // Deactivating and undelegated stake is neither delegated to the voter nor counted
+    for index in [1, 2] {
+        assert_eq!(
+            run(Check::Voter(voter), &mut accounts, index).unwrap_err(),
+            ZipperError::VoterMismatch.into()
+        );
+        assert!(run(Check::Staker(authority), &mut accounts, index).is_ok());
+    }
+    let comparisons = [
+        Comparison::Exact,
+        Comparison::Exact,
+        Comparison::Exact,
+        Comparison::Any,
+    ];
+    assert!(verify(&mut accounts, &[1_000_000, 0, 0, 0], &comparisons, &[], &[]).is_ok());
+
+    // Uninitialized stake accounts are rejected
+    assert_eq!(
+        verify(&mut accounts, &[0, 0, 0, 0], &[], &[], &[]).unwrap_err(),
+        ZipperError::NonSOLOrSPLAccountProvided.into()
+    );
+    assert_eq!(
+        run(Check::Staker(authority), &mut accounts, 3).unwrap_err(),
+        ZipperError::NotAStakeAccount.into()
+    );
+}
+
+#[test]
+fn nft() {
+    let owner = Pubkey::new_unique();
+    let mut accounts = vec![
+        TestAccount::mint(mint(1, 0)),
+        TestAccount::mint(mint(2, 0)),
+        TestAccount::mint(mint(1, 6)),
+    ];
+    let mints: Vec<Pubkey> = accounts.iter().map(|account| account.key).collect();
+    accounts.extend([
+        TestAccount::token(token_account(mints[0], owner, 1)),
+        TestAccount::token(token_account(mints[0], owner, 0)),
+        TestAccount::token(token_account(mints[1], owner, 1)),
+        TestAccount::token(token_account(mints[2], owner, 1)),
+    ]);
+    let nft = |mint_index| Check::Nft { mint_index, owner };
+
+    assert!(run(nft(0), &mut accounts, 3).is_ok());
+    assert_eq!(
+        run(
+            Check::Nft {
+                mint_index: 0,
+                owner: Pubkey::new_unique()
+            },
+            &mut accounts,
+            3
+        )
+        .unwrap_err(),
+        ZipperError::OwnerMismatch.into()
+    );
+    // The mint at `mint_index` must be the mint of the token account
+    assert_eq!(
+        run(nft(1), &mut accounts, 3).unwrap_err(),
+        ZipperError::MintMismatch.into()
+    );
+
+    // No token held, a supply of more than one and a mint with decimals
+    for (mint_index, index) in [(0, 4), (1, 5), (2, 6)] {
+        assert_eq!(
+            run(nft(mint_index), &mut accounts, index).unwrap_err(),
+            ZipperError::NotAnNft.into()
+        );
+    }
+
+    // The mint must be zipped, and be a mint
+    assert_eq!(
+        run(nft(7), &mut accounts, 3).unwrap_err(),
+        ZipperError::InvalidCheckIndex.into()
+    );
+    assert_eq!(
+        run(nft(3), &mut accounts, 3).unwrap_err(),
+        ZipperError::NotAMintAccount.into()
+    );
+}
+
+#[test]
+fn return_data_round_trip() {
+    let balances = [0, 1, 1_000_000_000, u64::MAX];
+    let data = return_data(balances.into_iter());
+    assert_eq!(data.len(), 8 * balances.len());
+    assert_eq!(&data[8..16], &1_u64.to_le_bytes());
+    assert_eq!(parse_return_data(&data), balances);
+    assert!(parse_return_data(&return_data([].into_iter())).is_empty());
+
+    // Balances past the cap are left out
+    let many: Vec<u64> = (0..MAX_RETURNED_BALANCES as u64 + 10).collect();
+    let data = return_data(many.iter().copied());
+    assert_eq!(data.len(), 1024);
+    assert_eq!(parse_return_data(&data), many[..MAX_RETURNED_BALANCES]);
+
+    // Trailing bytes that do not make up a balance are ignored
+    assert_eq!(parse_return_data(&data[..12]), [0]);
+}
+
+#[test]
+fn dry_run() {
+    let owner = Pubkey::new_unique();
+    let mut accounts = [
+        TestAccount::sol(10),
+        TestAccount::new(Pubkey::new_unique(), 5, vec![1, 2, 3]),
+    ];
+    let checks = [
+        AccountCheck {
+            index: 0,
+            check: Check::ProgramOwner(owner),
+        },
+        // Out of range
+        AccountCheck {
+            index: 2,
+            check: Check::ProgramOwner(owner),
+        },
+    ];
+    let groups = [BalanceGroup {
+        indices: vec![0, 2],
+        comparison: Comparison::AtLeast,
+        amount: 0,
+    }];
+    let verify_with = |accounts: &mut [TestAccount], balances: &[u64], dry_run: bool| {
+        let accs: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+        verify_accounts(&accs, balances, &[], &checks, &groups, dry_run)
+    };
+
+    // Failed balances, failed checks and out of range indices are only logged, and an
+    // account of any program is accepted
+    assert!(verify_with(&mut accounts, &[11, 6], true).is_ok());
+    assert_eq!(
+        verify_with(&mut accounts, &[10, 5], false).unwrap_err(),
+        ZipperError::NonSOLOrSPLAccountProvided.into()
+    );
+    assert_eq!(
+        verify_with(&mut accounts[..1], &[10], false).unwrap_err(),
+        ZipperError::ProgramOwnerMismatch.into()
+    );
+
+    // The number of accounts and balances must still match
+    assert_eq!(
+        verify_with(&mut accounts, &[10], true).unwrap_err(),
+        ZipperError::InvalidNumberOfAccountsOrBalances.into()
+    );
+}
@@ -0,0 +1,96 @@
+use std::rc::Rc;
+
+use anchor_client::{
+    solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig},
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        signature::{read_keypair_file, Keypair},
+        signer::Signer,
+        transaction::Transaction,
+    },
+    Client, Cluster, Program,
+};
+use anchor_lang::prelude::Pubkey;
+use zipper::{packed, AccountZipper, Comparison, ID as PROGRAM_ID};
+
+/// Compute units available to a single instruction by default
+const INSTRUCTION_COMPUTE_BUDGET: u64 = 200_000;
+
+/// Compares the compute units consumed by `verify` and the packed verify for an
+/// increasing number of zipped accounts. Requires a local validator with zipper deployed.
+#[test]
+fn compute_units() {
+    let dev_key: Keypair = read_keypair_file(&*shellexpand::tilde("../../dev_key.json"))
+        .expect("Example requires a keypair file");
+    let client: Client = Client::new_with_options(
+        Cluster::Localnet,
+        Rc::new(Keypair::from_bytes(dev_key.to_bytes().as_ref()).unwrap()),
+        CommitmentConfig::processed(),
+    );
+    let program: Program = client.program(PROGRAM_ID);
+    let solana_client: RpcClient = program.rpc();
+
+    println!("accounts |  verify | packed | packed + log");
+    let mut previous: Option<[u64; 3]> = None;
+    for num_accounts in [1, 2, 4, 8, 16] {
+        // Nonexistent accounts are system accounts with no lamports
+        let keys: Vec<Pubkey> = (0..num_accounts).map(|_| Pubkey::new_unique()).collect();
+        let balances = vec![0; num_accounts];
+        let comparisons = vec![Comparison::AtLeast; num_accounts];
+
+        let verify: Instruction = program
+            .request()
+            .accounts(AccountZipper::zip_accounts(&keys))
+            .args(zipper::instruction::Verify {
+                balances: balances.clone(),
+            })
+            .instructions()
+            .unwrap()
+            .remove(0);
+        let packed = packed::instruction(&keys, &balances, &comparisons, false);
+        let packed_with_log = packed::instruction(&keys, &balances, &comparisons, true);
+
+        let units = [verify, packed, packed_with_log]
+            .map(|ix| units_consumed(&solana_client, &dev_key.pubkey(), ix));
+        println!(
+            "{:>8} | {:>7} | {:>6} | {:>12}",
+            num_accounts, units[0], units[1], units[2]
+        );
+        // Every variant fits in the default budget, the packed verify is the cheapest and
+        // logging is not free
+        assert!(units
+            .iter()
+            .all(|&consumed| consumed < INSTRUCTION_COMPUTE_BUDGET));
+        assert!(units[1] < units[0]);
+        assert!(units[1] < units[2]);
+        if let Some(previous) = previous {
+            // Marginal cost per account since the previous row
+            let added = (num_accounts / 2) as u64;
+            println!(
+                "     +1  | {:>7} | {:>6} | {:>12}",
+                (units[0] - previous[0]) / added,
+                (units[1] - previous[1]) / added,
+                (units[2] - previous[2]) / added,
+            );
+        }
+        previous = Some(units);
+    }
+}
+
+fn units_consumed(solana_client: &RpcClient, payer: &Pubkey, ix: Instruction) -> u64 {
+    let transaction = Transaction::new_with_payer(&[ix], Some(payer));
+    let result = solana_client
+        .simulate_transaction_with_config(
+            &transaction,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .expect("failed to simulate transaction")
+        .value;
+    assert!(result.err.is_none(), "{:#?}", result.logs);
+    result.units_consumed.expect("missing units consumed")
+}
@@ -0,0 +1,28 @@
+use anchor_lang::prelude::Clock;
+use zipper::{deadline, ZipperError};
+
+#[test]
+fn deadlines() {
+    let clock = Clock {
+        slot: 100,
+        unix_timestamp: 1_700_000_000,
+        ..Clock::default()
+    };
+
+    // No bounds, and inclusive bounds
+    assert!(deadline::check(&clock, None, None, None).is_ok());
+    assert!(deadline::check(&clock, Some(100), Some(1_700_000_000), Some(100)).is_ok());
+
+    assert_eq!(
+        deadline::check(&clock, Some(99), None, None).unwrap_err(),
+        ZipperError::SlotDeadlineExceeded.into()
+    );
+    assert_eq!(
+        deadline::check(&clock, None, Some(1_699_999_999), None).unwrap_err(),
+        ZipperError::TimestampDeadlineExceeded.into()
+    );
+    assert_eq!(
+        deadline::check(&clock, None, None, Some(101)).unwrap_err(),
+        ZipperError::SlotTooEarly.into()
+    );
+}
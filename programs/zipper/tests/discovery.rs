@@ -0,0 +1,134 @@
+use anchor_client::solana_sdk::{account::Account, system_instruction};
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{instruction::Instruction, program_pack::Pack, system_program},
+};
+use anchor_spl::token::spl_token::{
+    self,
+    instruction::transfer,
+    state::{Account as TokenAccount, AccountState},
+};
+use zipper::client::{discover_with, DiscoveredAccount, Reason};
+
+fn wallet(lamports: u64) -> Account {
+    Account {
+        lamports,
+        data: vec![],
+        owner: system_program::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+        mint,
+        owner,
+        amount,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    Account {
+        lamports: 2_039_280,
+        data,
+        owner: spl_token::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+#[test]
+fn discover_user_accounts() {
+    let user = Pubkey::new_unique();
+    let rugger = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let user_ata = Pubkey::new_unique();
+    let rugger_ata = Pubkey::new_unique();
+    let user_readonly_ata = Pubkey::new_unique();
+    let uninitialized = Pubkey::new_unique();
+
+    let mut read_only = transfer(
+        &spl_token::ID,
+        &user_readonly_ata,
+        &rugger_ata,
+        &user,
+        &[],
+        1,
+    )
+    .unwrap();
+    read_only.accounts[0].is_writable = false;
+    let instructions: Vec<Instruction> = vec![
+        transfer(&spl_token::ID, &user_ata, &rugger_ata, &user, &[], 1).unwrap(),
+        read_only,
+        system_instruction::transfer(&user, &rugger, 1),
+        system_instruction::transfer(&user, &uninitialized, 1),
+    ];
+
+    let discovery = discover_with(&instructions, &user, |key| match key {
+        key if *key == user || *key == rugger => Some(wallet(1_000_000)),
+        key if *key == user_ata || *key == user_readonly_ata => {
+            Some(token_account(mint, user, 100))
+        }
+        key if *key == rugger_ata => Some(token_account(mint, rugger, 100)),
+        _ => None,
+    });
+
+    // The user's wallet comes first as the fee payer, the rugger's accounts, the
+    // read-only account and the missing account are left out
+    assert_eq!(
+        discovery.accounts,
+        vec![
+            DiscoveredAccount {
+                key: user,
+                reason: Reason::UserWallet,
+            },
+            DiscoveredAccount {
+                key: user_ata,
+                reason: Reason::UserTokenAccount { mint },
+            },
+        ]
+    );
+    assert_eq!(discovery.keys(), vec![user, user_ata]);
+    assert!(discovery
+        .zip_accounts()
+        .iter()
+        .all(|meta| !meta.is_signer && !meta.is_writable));
+    assert_eq!(
+        discovery.rationale(),
+        vec![
+            format!("{}: writable wallet of the user", user),
+            format!(
+                "{}: writable token account of mint {} owned by the user",
+                user_ata, mint
+            ),
+        ]
+    );
+}
+
+#[test]
+fn discover_payer_signing_readonly() {
+    let user = Pubkey::new_unique();
+    let rugger = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let user_ata = Pubkey::new_unique();
+    let rugger_ata = Pubkey::new_unique();
+
+    // The user only signs as the read-only owner, but pays the fee as the payer
+    let instructions: Vec<Instruction> =
+        vec![transfer(&spl_token::ID, &user_ata, &rugger_ata, &user, &[], 1).unwrap()];
+    assert!(instructions[0]
+        .accounts
+        .iter()
+        .any(|meta| meta.pubkey == user && meta.is_signer && !meta.is_writable));
+
+    let discovery = discover_with(&instructions, &user, |key| match key {
+        key if *key == user || *key == rugger => Some(wallet(1_000_000)),
+        key if *key == user_ata => Some(token_account(mint, user, 100)),
+        key if *key == rugger_ata => Some(token_account(mint, rugger, 100)),
+        _ => None,
+    });
+    assert_eq!(discovery.keys(), vec![user, user_ata]);
+    assert_eq!(discovery.accounts[0].reason, Reason::UserWallet);
+}
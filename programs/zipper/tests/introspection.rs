@@ -0,0 +1,181 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::Instruction,
+        sysvar::{
+            self,
+            instructions::{
+                construct_instructions_data, store_current_index, BorrowedAccountMeta,
+                BorrowedInstruction,
+            },
+        },
+    },
+    InstructionData,
+};
+use zipper::{introspection, packed, Comparison, ZipperError, ID as PROGRAM_ID};
+
+/// The data of the Instructions sysvar in a transaction of `instructions`, while the
+/// top-level instruction at `current` executes
+fn instructions_sysvar(instructions: &[Instruction], current: u16) -> Vec<u8> {
+    let borrowed: Vec<BorrowedInstruction> = instructions
+        .iter()
+        .map(|instruction| BorrowedInstruction {
+            program_id: &instruction.program_id,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| BorrowedAccountMeta {
+                    pubkey: &meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: &instruction.data,
+        })
+        .collect();
+    let mut data = construct_instructions_data(&borrowed);
+    store_current_index(&mut data, current);
+    data
+}
+
+/// Runs `f` on an Instructions sysvar account at `key` holding `data`
+fn with_sysvar<T>(key: Pubkey, mut data: Vec<u8>, f: impl FnOnce(&AccountInfo) -> T) -> T {
+    let mut lamports = 0;
+    let owner = sysvar::ID;
+    let account = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    f(&account)
+}
+
+fn noop(program_id: Pubkey) -> Instruction {
+    Instruction::new_with_bytes(program_id, &[1, 2, 3], vec![])
+}
+
+#[test]
+fn top_level() {
+    let other_program = Pubkey::new_unique();
+    let instructions = [noop(other_program), noop(PROGRAM_ID)];
+
+    // Invoked by the transaction
+    let data = instructions_sysvar(&instructions, 1);
+    with_sysvar(sysvar::instructions::ID, data, |sysvar| {
+        assert!(introspection::assert_top_level(sysvar).is_ok())
+    });
+
+    // Invoked through a CPI while another program's instruction executes
+    let data = instructions_sysvar(&instructions, 0);
+    with_sysvar(sysvar::instructions::ID, data, |sysvar| {
+        assert_eq!(
+            introspection::assert_top_level(sysvar).unwrap_err(),
+            ZipperError::NotTopLevelInstruction.into()
+        )
+    });
+
+    // Any other account is rejected
+    let data = instructions_sysvar(&instructions, 1);
+    with_sysvar(Pubkey::new_unique(), data, |sysvar| {
+        assert!(introspection::assert_top_level(sysvar).is_err())
+    });
+}
+
+#[test]
+fn allowed_programs() {
+    let (swap, token) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let instructions = [noop(swap), noop(token), noop(PROGRAM_ID)];
+    let data = instructions_sysvar(&instructions, 2);
+
+    with_sysvar(sysvar::instructions::ID, data, |sysvar| {
+        // The zipper program itself is always allowed
+        assert!(introspection::assert_allowed_programs(sysvar, &[swap, token]).is_ok());
+        assert!(introspection::assert_allowed_programs(sysvar, &[token, swap, PROGRAM_ID]).is_ok());
+        assert_eq!(
+            introspection::assert_allowed_programs(sysvar, &[swap]).unwrap_err(),
+            ZipperError::ProgramNotAllowed.into()
+        );
+        assert_eq!(
+            introspection::assert_allowed_programs(sysvar, &[]).unwrap_err(),
+            ZipperError::ProgramNotAllowed.into()
+        );
+    });
+
+    // Any other account is rejected
+    let data = instructions_sysvar(&instructions, 2);
+    with_sysvar(Pubkey::new_unique(), data, |sysvar| {
+        assert_eq!(
+            introspection::assert_allowed_programs(sysvar, &[swap, token]).unwrap_err(),
+            ZipperError::InvalidInstructionsSysvar.into()
+        )
+    });
+}
+
+#[test]
+fn last_instruction() {
+    let other_program = Pubkey::new_unique();
+    let zipper_ix = |data: Vec<u8>| Instruction::new_with_bytes(PROGRAM_ID, &data, vec![]);
+    let last = zipper_ix(zipper::instruction::VerifyLastInstruction { allow_zipper: true }.data());
+    let verify = zipper_ix(zipper::instruction::Verify { balances: vec![] }.data());
+    let verify_with = zipper_ix(
+        zipper::instruction::VerifyWith {
+            balances: vec![],
+            comparisons: vec![],
+            checks: vec![],
+            groups: vec![],
+        }
+        .data(),
+    );
+    let packed_verify = zipper_ix(packed::encode(&[1], &[Comparison::AtLeast], false));
+    let snapshot = zipper_ix(zipper::instruction::Snapshot { num_accounts: 0 }.data());
+    let dry_run = zipper_ix(
+        zipper::instruction::VerifyDryRun {
+            balances: vec![],
+            comparisons: vec![],
+            checks: vec![],
+            groups: vec![],
+        }
+        .data(),
+    );
+
+    let assert_last = |instructions: &[Instruction], allow_zipper: bool| {
+        let data = instructions_sysvar(instructions, 1);
+        with_sysvar(sysvar::instructions::ID, data, |sysvar| {
+            introspection::assert_last_instruction(sysvar, allow_zipper)
+        })
+    };
+    let before = noop(other_program);
+
+    // Last in the transaction
+    for allow_zipper in [false, true] {
+        assert!(assert_last(&[before.clone(), last.clone()], allow_zipper).is_ok());
+    }
+
+    // Followed by zipper verify instructions
+    for after in [&verify, &verify_with, &packed_verify] {
+        let instructions = [before.clone(), last.clone(), after.clone(), last.clone()];
+        assert!(assert_last(&instructions, true).is_ok());
+        assert_eq!(
+            assert_last(&instructions, false).unwrap_err(),
+            ZipperError::NotLastInstruction.into()
+        );
+    }
+
+    // Followed by any other instruction, including zipper instructions that do not only
+    // assert on the state of the transaction
+    for after in [&before, &snapshot, &dry_run, &noop(PROGRAM_ID)] {
+        let instructions = [before.clone(), last.clone(), after.clone()];
+        assert_eq!(
+            assert_last(&instructions, true).unwrap_err(),
+            ZipperError::NotLastInstruction.into()
+        );
+    }
+    assert!(!introspection::is_verify_instruction(
+        &Instruction::new_with_bytes(other_program, &verify.data, vec![])
+    ));
+}
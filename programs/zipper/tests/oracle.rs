@@ -0,0 +1,206 @@
+use anchor_lang::{prelude::*, solana_program::program_pack::Pack};
+use anchor_spl::token::spl_token::{
+    self,
+    state::{Account as SplAccount, AccountState},
+};
+use zipper::{
+    oracle::{
+        check_value, PriceFeed, ACCOUNT_TYPE_PRICE, MAGIC, MIN_PRICE_ACCOUNT_LEN, STATUS_TRADING,
+        VERSION,
+    },
+    ZipperError,
+};
+
+const PYTH_PRICE_ACCOUNT_LEN: usize = 3312;
+
+/// Crafts the data of a Pyth v2 price account with the given aggregate price
+fn price_account(price: i64, conf: u64, expo: i32, status: u32, pub_slot: u64) -> Vec<u8> {
+    let mut data = vec![0; PYTH_PRICE_ACCOUNT_LEN];
+    data[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+    data[4..8].copy_from_slice(&VERSION.to_le_bytes());
+    data[8..12].copy_from_slice(&ACCOUNT_TYPE_PRICE.to_le_bytes());
+    data[20..24].copy_from_slice(&expo.to_le_bytes());
+    data[208..216].copy_from_slice(&price.to_le_bytes());
+    data[216..224].copy_from_slice(&conf.to_le_bytes());
+    data[224..228].copy_from_slice(&status.to_le_bytes());
+    data[232..240].copy_from_slice(&pub_slot.to_le_bytes());
+    data
+}
+
+#[test]
+fn parse_price_account() {
+    let data = price_account(20_000_000_000, 10_000_000, -8, STATUS_TRADING, 100);
+    assert_eq!(
+        PriceFeed::parse(&data).unwrap(),
+        PriceFeed {
+            price: 20_000_000_000,
+            conf: 10_000_000,
+            expo: -8,
+            status: STATUS_TRADING,
+            pub_slot: 100,
+        }
+    );
+
+    // Truncated, wrong magic and wrong account type
+    assert!(PriceFeed::parse(&data[..MIN_PRICE_ACCOUNT_LEN - 1]).is_err());
+    let mut bad_magic = data.clone();
+    bad_magic[0] ^= 1;
+    assert!(PriceFeed::parse(&bad_magic).is_err());
+    let mut product_account = data;
+    product_account[8..12].copy_from_slice(&2_u32.to_le_bytes());
+    assert!(PriceFeed::parse(&product_account).is_err());
+}
+
+#[test]
+fn validate_price() {
+    // $200.00 ± $0.10, i.e. 5 bps
+    let price = PriceFeed::parse(&price_account(
+        20_000_000_000,
+        10_000_000,
+        -8,
+        STATUS_TRADING,
+        100,
+    ))
+    .unwrap();
+    assert!(price.validate(110, 10, 5).is_ok());
+
+    // Stale
+    assert!(price.validate(111, 10, 5).is_err());
+    // Confidence interval too wide
+    assert!(price.validate(110, 10, 4).is_err());
+    // Not trading
+    let halted = PriceFeed { status: 2, ..price };
+    assert!(halted.validate(110, 10, 5).is_err());
+    // Negative price
+    let negative = PriceFeed { price: -1, ..price };
+    assert!(negative.validate(110, 10, 5).is_err());
+}
+
+#[test]
+fn value_balances() {
+    // $200.00 ± $0.10 per SOL
+    let price = PriceFeed::parse(&price_account(
+        20_000_000_000,
+        10_000_000,
+        -8,
+        STATUS_TRADING,
+        100,
+    ))
+    .unwrap();
+
+    // 1.5 SOL is worth $299.85 at the lower end of the confidence interval
+    assert_eq!(price.value(1_500_000_000, 9, -2).unwrap(), 29_985);
+    assert_eq!(price.value(1_500_000_000, 9, 0).unwrap(), 299);
+    assert_eq!(price.value(1_500_000_000, 9, -6).unwrap(), 299_850_000);
+
+    // A price with a positive exponent scales up
+    let thousands = PriceFeed {
+        price: 3,
+        conf: 0,
+        expo: 3,
+        ..price
+    };
+    assert_eq!(thousands.value(2, 0, 0).unwrap(), 6_000);
+
+    // Overflowing values are rejected rather than wrapped
+    let huge = PriceFeed {
+        price: i64::MAX,
+        conf: 0,
+        expo: 30,
+        ..price
+    };
+    assert!(huge.value(u64::MAX, 0, 0).is_err());
+}
+
+/// An account held by the test, from which the `AccountInfo`s given to `check_value`
+/// are borrowed
+struct TestAccount {
+    key: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+}
+
+impl TestAccount {
+    fn new(owner: Pubkey, lamports: u64, data: Vec<u8>) -> TestAccount {
+        TestAccount {
+            key: Pubkey::new_unique(),
+            lamports,
+            data,
+            owner,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            false,
+            false,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+#[test]
+fn portfolio_value() {
+    let oracle_program = Pubkey::new_unique();
+    let mut token_data = vec![0; SplAccount::LEN];
+    SplAccount {
+        mint: Pubkey::new_unique(),
+        owner: Pubkey::new_unique(),
+        // 100 USDC
+        amount: 100_000_000,
+        state: AccountState::Initialized,
+        ..SplAccount::default()
+    }
+    .pack_into_slice(&mut token_data);
+    let mut accounts = [
+        // 1.5 SOL at $200.00 ± $0.10
+        TestAccount::new(System::id(), 1_500_000_000, vec![]),
+        TestAccount::new(
+            oracle_program,
+            1,
+            price_account(20_000_000_000, 10_000_000, -8, STATUS_TRADING, 100),
+        ),
+        // 100 USDC at $1.00
+        TestAccount::new(spl_token::ID, 2_039_280, token_data),
+        TestAccount::new(
+            oracle_program,
+            1,
+            price_account(100_000_000, 0, -8, STATUS_TRADING, 100),
+        ),
+    ];
+    let accs: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+    let check = |min_value: u64, decimals: &[u8], oracle_program: &Pubkey, slot: u64| {
+        check_value(&accs, slot, min_value, -2, decimals, oracle_program, 10, 5)
+    };
+
+    // $299.85 of SOL and $100.00 of USDC, in cents
+    assert!(check(39_985, &[9, 6], &oracle_program, 110).is_ok());
+    assert_eq!(
+        check(39_986, &[9, 6], &oracle_program, 110).unwrap_err(),
+        ZipperError::InsufficientValue.into()
+    );
+
+    // Every pair needs its decimals
+    assert_eq!(
+        check(0, &[9], &oracle_program, 110).unwrap_err(),
+        ZipperError::InvalidNumberOfAccountsOrBalances.into()
+    );
+
+    // Price accounts of any other program are rejected, even with a valid layout
+    assert_eq!(
+        check(0, &[9, 6], &Pubkey::new_unique(), 110).unwrap_err(),
+        ZipperError::InvalidPriceAccount.into()
+    );
+
+    // Every price is validated at the given slot
+    assert_eq!(
+        check(0, &[9, 6], &oracle_program, 111).unwrap_err(),
+        ZipperError::StaleOraclePrice.into()
+    );
+}
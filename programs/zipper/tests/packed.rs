@@ -0,0 +1,99 @@
+use anchor_lang::prelude::*;
+use zipper::{
+    packed::{self, FLAG_LOG, FLAG_VARINT, PACKED_VERIFY_TAG},
+    Comparison, ZipperError,
+};
+
+/// Runs the packed verify of `data`, with its tag, against a system account holding
+/// `lamports`
+fn verify(lamports: u64, data: &[u8]) -> Result<()> {
+    let key = Pubkey::new_unique();
+    let owner = System::id();
+    let mut lamports = lamports;
+    let mut account_data = vec![];
+    let account = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut account_data,
+        &owner,
+        false,
+        0,
+    );
+    let data = data
+        .strip_prefix(&PACKED_VERIFY_TAG)
+        .expect("missing packed verify tag");
+    packed::verify(&[account], data)
+}
+
+#[test]
+fn encoding_round_trip() {
+    for comparison in [
+        Comparison::AtLeast,
+        Comparison::AtMost,
+        Comparison::Exact,
+        Comparison::Within { max: u64::MAX },
+        Comparison::Any,
+    ] {
+        for log in [false, true] {
+            let data = packed::encode(&[1_000], &[comparison], log);
+            assert!(verify(1_000, &data).is_ok());
+        }
+    }
+
+    // Bounds of 8 or more varint bytes are written as fixed width
+    let data = packed::encode(&[u64::MAX], &[Comparison::AtMost], false);
+    assert_eq!(data[PACKED_VERIFY_TAG.len() + 1] & FLAG_VARINT, 0);
+    assert!(verify(1_000, &data).is_ok());
+    assert_eq!(
+        verify(
+            1_001,
+            &packed::encode(&[1_000], &[Comparison::AtMost], false)
+        )
+        .unwrap_err(),
+        ZipperError::ExcessiveBalance.into()
+    );
+}
+
+#[test]
+fn reserved_flags() {
+    let data = packed::encode(&[1_000], &[Comparison::AtLeast], false);
+    let header = PACKED_VERIFY_TAG.len();
+    let entry = header + 1;
+
+    // Header flags other than `FLAG_LOG` are reserved
+    for flag in 1..8 {
+        let mut data = data.clone();
+        data[header] |= 1 << flag;
+        assert_eq!(
+            verify(1_000, &data).unwrap_err(),
+            ZipperError::InvalidPackedData.into()
+        );
+    }
+    let mut logged = data.clone();
+    logged[header] |= FLAG_LOG;
+    assert!(verify(1_000, &logged).is_ok());
+
+    // Entry flags above the comparison and `FLAG_VARINT` are reserved, even for an entry
+    // without bounds
+    let any = packed::encode(&[0], &[Comparison::Any], false);
+    for flag in 4..8 {
+        for data in [&data, &any] {
+            let mut data = data.clone();
+            data[entry] |= 1 << flag;
+            assert_eq!(
+                verify(1_000, &data).unwrap_err(),
+                ZipperError::InvalidPackedData.into()
+            );
+        }
+    }
+
+    // Unknown comparisons are rejected as well
+    let mut unknown = data;
+    unknown[entry] = (unknown[entry] & FLAG_VARINT) | 0b101;
+    assert_eq!(
+        verify(1_000, &unknown).unwrap_err(),
+        ZipperError::InvalidPackedData.into()
+    );
+}
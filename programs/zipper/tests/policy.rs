@@ -0,0 +1,84 @@
+use anchor_lang::prelude::Pubkey;
+use zipper::client::{Policy, PostBalance, Tolerance};
+
+#[test]
+fn tolerances() {
+    assert_eq!(Tolerance::default().apply(1_000), 1_000);
+    assert_eq!(Tolerance::Absolute(100).apply(1_000), 900);
+    assert_eq!(Tolerance::Absolute(2_000).apply(1_000), 0);
+    assert_eq!(Tolerance::BasisPoints(50).apply(1_000_000), 995_000);
+    assert_eq!(Tolerance::BasisPoints(20_000).apply(1_000), 0);
+
+    // The slack is rounded down, so the bound never drops below what was asked for
+    assert_eq!(Tolerance::BasisPoints(1).apply(9_999), 9_999);
+    assert_eq!(Tolerance::BasisPoints(10_000).apply(u64::MAX), 0);
+}
+
+#[test]
+fn policy_bounds() {
+    let payer = Pubkey::new_unique();
+    let other_wallet = Pubkey::new_unique();
+    let usdc = Pubkey::new_unique();
+    let bonk = Pubkey::new_unique();
+    let post_balances = [
+        PostBalance {
+            key: payer,
+            mint: None,
+            balance: 5_906_440,
+        },
+        PostBalance {
+            key: other_wallet,
+            mint: None,
+            balance: 1_000_000,
+        },
+        PostBalance {
+            key: Pubkey::new_unique(),
+            mint: Some(usdc),
+            balance: 99_000_000,
+        },
+        PostBalance {
+            key: Pubkey::new_unique(),
+            mint: Some(bonk),
+            balance: 100_000_000,
+        },
+    ];
+
+    // By default, no account may decrease below its simulated post-balance
+    assert_eq!(
+        Policy::default().bounds(&payer, &post_balances),
+        vec![5_906_440, 1_000_000, 99_000_000, 100_000_000]
+    );
+
+    // The fee allowance only applies to the payer, mint tolerances take precedence over
+    // the token tolerance
+    let policy = Policy::default()
+        .sol_tolerance(Tolerance::Absolute(1_000))
+        .fee_allowance(5_000)
+        .token_tolerance(Tolerance::BasisPoints(100))
+        .mint_tolerance(usdc, Tolerance::Absolute(10));
+    assert_eq!(
+        policy.bounds(&payer, &post_balances),
+        vec![5_900_440, 999_000, 98_999_990, 99_000_000]
+    );
+    assert_eq!(policy.tolerance(None), Tolerance::Absolute(1_000));
+    assert_eq!(policy.tolerance(Some(&usdc)), Tolerance::Absolute(10));
+    assert_eq!(policy.tolerance(Some(&bonk)), Tolerance::BasisPoints(100));
+
+    // The mint tolerance is taken off that mint's post-balance, and the fee allowance
+    // comes off the payer's SOL bound on top of the SOL tolerance, but not off any other
+    // wallet's
+    assert_eq!(policy.bound(&payer, &post_balances[2]), 99_000_000 - 10);
+    assert_eq!(
+        policy.bound(&payer, &post_balances[0]),
+        5_906_440 - 1_000 - 5_000
+    );
+    assert_eq!(policy.bound(&payer, &post_balances[1]), 1_000_000 - 1_000);
+
+    // Bounds saturate at zero
+    let drained = PostBalance {
+        key: payer,
+        mint: None,
+        balance: 4_000,
+    };
+    assert_eq!(policy.bound(&payer, &drained), 0);
+}
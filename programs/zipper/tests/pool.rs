@@ -0,0 +1,35 @@
+use zipper::pool::{price, price_from_f64};
+
+const ONE: u128 = 1 << 64;
+
+#[test]
+fn pool_prices() {
+    assert_eq!(price(1, 1), Some(ONE));
+    assert_eq!(price(2, 1), Some(ONE / 2));
+    assert_eq!(price(1, 2), Some(2 * ONE));
+    // Rounds down
+    assert_eq!(price(3, 1), Some(ONE / 3));
+    assert_eq!(price(u64::MAX, 1), Some(1));
+
+    // An empty first reserve has no price, an empty second one a price of zero
+    assert_eq!(price(0, 1), None);
+    assert_eq!(price(0, 0), None);
+    assert_eq!(price(1, 0), Some(0));
+
+    // The largest reserves do not overflow
+    assert_eq!(price(u64::MAX, u64::MAX), Some(ONE));
+    assert_eq!(price(1, u64::MAX), Some((u64::MAX as u128) << 64));
+}
+
+#[test]
+fn prices_from_f64() {
+    assert_eq!(price_from_f64(1.0), ONE);
+    assert_eq!(price_from_f64(0.5), ONE / 2);
+    assert_eq!(price_from_f64(1.5), price(2, 3).unwrap());
+    assert_eq!(price_from_f64(0.0), 0);
+
+    // Out of range prices saturate
+    assert_eq!(price_from_f64(-1.0), 0);
+    assert_eq!(price_from_f64(f64::NAN), 0);
+    assert_eq!(price_from_f64(1e30), u128::MAX);
+}
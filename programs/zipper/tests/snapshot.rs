@@ -0,0 +1,120 @@
+use std::rc::Rc;
+
+use anchor_client::{
+    solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig},
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        signature::{read_keypair_file, Keypair},
+        signer::Signer,
+        system_instruction, system_program, sysvar,
+        transaction::{Transaction, TransactionError},
+    },
+    Client, Cluster, Program,
+};
+use anchor_lang::{prelude::Pubkey, solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE};
+use zipper::{
+    AccountZipper, BalanceSnapshot, ZipperError, ID as PROGRAM_ID, MAX_SNAPSHOT_ACCOUNTS,
+};
+
+const TRANSFER: u64 = 1_000_000;
+
+#[test]
+fn snapshot_space() {
+    // The snapshot account is created through a CPI, which allocates at most 10 KiB
+    assert!(BalanceSnapshot::space(MAX_SNAPSHOT_ACCOUNTS).unwrap() <= MAX_PERMITTED_DATA_INCREASE);
+    assert_eq!(
+        BalanceSnapshot::space(MAX_SNAPSHOT_ACCOUNTS + 1).unwrap_err(),
+        ZipperError::TooManySnapshotAccounts.into()
+    );
+}
+
+/// Snapshots the payer and a recipient, transfers lamports between them and verifies the
+/// deltas in a single transaction. Requires a local validator with zipper deployed.
+#[test]
+fn snapshot_delta() {
+    let dev_key: Keypair = read_keypair_file(&*shellexpand::tilde("../../dev_key.json"))
+        .expect("Example requires a keypair file");
+    let client: Client = Client::new_with_options(
+        Cluster::Localnet,
+        Rc::new(Keypair::from_bytes(dev_key.to_bytes().as_ref()).unwrap()),
+        CommitmentConfig::processed(),
+    );
+    let program: Program = client.program(PROGRAM_ID);
+    let solana_client: RpcClient = program.rpc();
+    let payer = dev_key.pubkey();
+    let recipient = Pubkey::new_unique();
+    let keys = [payer, recipient];
+
+    let snapshot: Instruction = program
+        .request()
+        .accounts(zipper::accounts::TakeSnapshot {
+            authority: payer,
+            snapshot: AccountZipper::snapshot_address(&payer),
+            instructions: sysvar::instructions::ID,
+            system_program: system_program::ID,
+        })
+        .accounts(AccountZipper::zip_accounts(&keys))
+        .args(zipper::instruction::Snapshot { num_accounts: 2 })
+        .instructions()
+        .unwrap()
+        .remove(0);
+    let transfer = system_instruction::transfer(&payer, &recipient, TRANSFER);
+    let verify_delta = |deltas: Vec<i64>| -> Instruction {
+        program
+            .request()
+            .accounts(zipper::accounts::VerifyDelta {
+                authority: payer,
+                snapshot: AccountZipper::snapshot_address(&payer),
+                instructions: sysvar::instructions::ID,
+            })
+            .accounts(AccountZipper::zip_accounts(&keys))
+            .args(zipper::instruction::VerifyDelta { deltas })
+            .instructions()
+            .unwrap()
+            .remove(0)
+    };
+
+    // The transaction fee and the rent of the snapshot account are paid before the
+    // balances are recorded, and the rent is refunded after they are verified, so only
+    // the transfer is seen
+    let spend = -(TRANSFER as i64);
+    let allowed = [
+        snapshot.clone(),
+        transfer.clone(),
+        verify_delta(vec![spend, TRANSFER as i64]),
+    ];
+    assert_eq!(simulate(&solana_client, &payer, &allowed), None);
+
+    // Spending one more lamport than allowed fails
+    let exceeded = [
+        snapshot.clone(),
+        transfer.clone(),
+        verify_delta(vec![spend + 1, TRANSFER as i64]),
+    ];
+    assert!(simulate(&solana_client, &payer, &exceeded).is_some());
+
+    // A delta requires a snapshot
+    let missing = [transfer, verify_delta(vec![spend, TRANSFER as i64])];
+    assert!(simulate(&solana_client, &payer, &missing).is_some());
+}
+
+fn simulate(
+    solana_client: &RpcClient,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+) -> Option<TransactionError> {
+    let transaction = Transaction::new_with_payer(instructions, Some(payer));
+    solana_client
+        .simulate_transaction_with_config(
+            &transaction,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .expect("failed to simulate transaction")
+        .value
+        .err
+}
@@ -18,7 +18,7 @@ use anchor_lang::prelude::Pubkey;
 use anchor_spl::token::{spl_token::instruction::transfer, Mint, ID as TOKEN_PROGRAM_ID};
 use anyhow::Result;
 use rand::rngs::OsRng;
-use zipper::{AccountZipper, ID as PROGRAM_ID};
+use zipper::{AccountCheck, AccountZipper, Check, ID as PROGRAM_ID};
 
 const DEMO_TOKEN_DECIMALS: u8 = 6;
 const ONE_DEMO_TOKEN: u64 = 10_u64.pow(DEMO_TOKEN_DECIMALS as u32);
@@ -425,13 +425,38 @@ fn spoof() {
             user.ata,
             user.ata2,
         ]))
-        .args(zipper::instruction::Verify {
+        .args(zipper::instruction::VerifyWith {
             balances: {
                 let mut balances = simulated_post_balances.to_vec();
                 // The first and second tx failed
                 balances[0] -= 2 * TX_FEE;
                 balances
             },
+            comparisons: vec![],
+            // Also bind the token balance checks to the expected mints and wallet
+            checks: vec![
+                AccountCheck {
+                    index: 1,
+                    check: Check::Mint(mint_key.pubkey()),
+                },
+                AccountCheck {
+                    index: 1,
+                    check: Check::Owner(user.keypair.pubkey()),
+                },
+                AccountCheck {
+                    index: 2,
+                    check: Check::Mint(mint_key2.pubkey()),
+                },
+                AccountCheck {
+                    index: 1,
+                    check: Check::NoDelegate,
+                },
+                AccountCheck {
+                    index: 1,
+                    check: Check::NoCloseAuthority,
+                },
+            ],
+            groups: vec![],
         })
         .instructions()
         .unwrap()
@@ -0,0 +1,323 @@
+use anchor_client::solana_sdk::{
+    hash::Hash,
+    message::{
+        v0::{self, MessageAddressTableLookup},
+        Message, MessageHeader, VersionedMessage,
+    },
+    signature::Signature,
+    transaction::{Transaction, VersionedTransaction},
+};
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::instruction::{AccountMeta, CompiledInstruction},
+};
+use anchor_spl::token::{spl_token::instruction::transfer, ID as TOKEN_PROGRAM_ID};
+use zipper::{
+    client::{
+        compile_v0_message, resolve_account_keys, verify_instruction, zip_serialized_transaction,
+        zip_transaction, zip_v0_message, LookupTable, ZipperClientError, LOOKUP_TABLE_META_SIZE,
+    },
+    ID as PROGRAM_ID,
+};
+
+#[test]
+fn zip_legacy_transaction() {
+    let payer = Pubkey::new_unique();
+    let user_ata = Pubkey::new_unique();
+    let rugger_ata = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let transfer_instruction =
+        transfer(&TOKEN_PROGRAM_ID, &user_ata, &rugger_ata, &payer, &[], 1).unwrap();
+    let transaction = Transaction::new_with_payer(&[transfer_instruction], Some(&payer));
+    let serialized = bincode::serialize(&VersionedTransaction {
+        signatures: transaction.signatures.clone(),
+        message: VersionedMessage::Legacy(transaction.message.clone()),
+    })
+    .unwrap();
+
+    let zipper_instruction = verify_instruction(
+        &[payer, user_ata, other],
+        vec![1, 2, 3],
+        vec![],
+        vec![],
+        vec![],
+    );
+    let zipped = zip_serialized_transaction(&serialized, &[], &zipper_instruction).unwrap();
+    let message = match &zipped.transaction.message {
+        VersionedMessage::Legacy(message) => message,
+        _ => panic!("expected a legacy message"),
+    };
+
+    // The zipper program and the missing account are appended as readonly unsigned keys,
+    // the existing keys and instruction are left untouched
+    let num_keys = transaction.message.account_keys.len();
+    assert_eq!(
+        message.account_keys[..num_keys],
+        transaction.message.account_keys
+    );
+    assert_eq!(message.account_keys[num_keys..], [PROGRAM_ID, other]);
+    assert_eq!(
+        message.header.num_required_signatures,
+        transaction.message.header.num_required_signatures
+    );
+    assert_eq!(
+        message.header.num_readonly_unsigned_accounts,
+        transaction.message.header.num_readonly_unsigned_accounts + 2
+    );
+    assert_eq!(message.instructions[0], transaction.message.instructions[0]);
+    let user_ata_index = transaction.message.account_keys[..]
+        .iter()
+        .position(|key| *key == user_ata)
+        .unwrap() as u8;
+    assert_eq!(
+        message.instructions[1],
+        CompiledInstruction {
+            program_id_index: num_keys as u8,
+            accounts: vec![0, user_ata_index, num_keys as u8 + 1],
+            data: zipper_instruction.data.clone(),
+        }
+    );
+
+    // Signatures are reset for the same signers
+    assert_eq!(zipped.transaction.signatures, vec![Signature::default()]);
+    assert_eq!(zipped.original_size, serialized.len());
+    assert_eq!(
+        zipped.zipped_size,
+        bincode::serialize(&zipped.transaction).unwrap().len()
+    );
+    assert!(zipped.overhead() > zipper_instruction.data.len() + 2 * 32);
+    assert!(zipped.fits_in_packet());
+}
+
+#[test]
+fn zip_v0_transaction() {
+    let payer = Pubkey::new_unique();
+    let user_ata = Pubkey::new_unique();
+    let rugger_ata = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let table = LookupTable {
+        key: Pubkey::new_unique(),
+        addresses: vec![mint, user_ata, rugger_ata],
+    };
+
+    // The token accounts are loaded as writable and the mint as readonly, after the
+    // static payer and token program
+    let transaction = VersionedTransaction {
+        signatures: vec![Signature::default()],
+        message: VersionedMessage::V0(v0::Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 1,
+            },
+            account_keys: vec![payer, TOKEN_PROGRAM_ID],
+            recent_blockhash: Hash::default(),
+            instructions: vec![CompiledInstruction {
+                program_id_index: 1,
+                accounts: vec![2, 3, 0],
+                data: vec![3, 1, 0, 0, 0, 0, 0, 0, 0],
+            }],
+            address_table_lookups: vec![MessageAddressTableLookup {
+                account_key: table.key,
+                writable_indexes: vec![1, 2],
+                readonly_indexes: vec![0],
+            }],
+        }),
+    };
+
+    let zipper_instruction = verify_instruction(
+        &[payer, user_ata, mint, other],
+        vec![1, 2, 3, 4],
+        vec![],
+        vec![],
+        vec![],
+    );
+    let zipped = zip_transaction(&transaction, &[table.clone()], &zipper_instruction).unwrap();
+    let message = match &zipped.transaction.message {
+        VersionedMessage::V0(message) => message,
+        _ => panic!("expected a v0 message"),
+    };
+
+    // Accounts from the lookup table are referenced through it, shifted past the two
+    // new static keys
+    assert_eq!(
+        message.account_keys,
+        vec![payer, TOKEN_PROGRAM_ID, PROGRAM_ID, other]
+    );
+    assert_eq!(message.header.num_readonly_unsigned_accounts, 3);
+    assert_eq!(message.instructions[0].accounts, vec![4, 5, 0]);
+    assert_eq!(
+        message.instructions[1],
+        CompiledInstruction {
+            program_id_index: 2,
+            accounts: vec![0, 4, 6, 3],
+            data: zipper_instruction.data.clone(),
+        }
+    );
+    assert_eq!(message.address_table_lookups.len(), 1);
+
+    // The lookup table must be provided
+    assert!(matches!(
+        zip_transaction(&transaction, &[], &zipper_instruction),
+        Err(ZipperClientError::MissingLookupTable(key)) if key == table.key
+    ));
+
+    // Write access to a readonly loaded account cannot be granted
+    let mut writable_mint = zipper_instruction.clone();
+    writable_mint.accounts[2] = AccountMeta::new(mint, false);
+    assert!(matches!(
+        zip_transaction(&transaction, &[table], &writable_mint),
+        Err(ZipperClientError::MissingPrivilege(key)) if key == mint
+    ));
+}
+
+#[test]
+fn reject_malformed_transaction() {
+    let payer = Pubkey::new_unique();
+    let user_ata = Pubkey::new_unique();
+    let rugger_ata = Pubkey::new_unique();
+    let transfer_instruction =
+        transfer(&TOKEN_PROGRAM_ID, &user_ata, &rugger_ata, &payer, &[], 1).unwrap();
+    let transaction = Transaction::new_with_payer(&[transfer_instruction], Some(&payer));
+    let zipper_instruction =
+        verify_instruction(&[payer, user_ata], vec![1, 2], vec![], vec![], vec![]);
+    let zip = |message: &Message| {
+        zip_transaction(
+            &VersionedTransaction {
+                signatures: transaction.signatures.clone(),
+                message: VersionedMessage::Legacy(message.clone()),
+            },
+            &[],
+            &zipper_instruction,
+        )
+    };
+    assert!(zip(&transaction.message).is_ok());
+
+    // Every signer is read-only, so there is no fee payer
+    let mut readonly_payer = transaction.message.clone();
+    readonly_payer.header.num_readonly_signed_accounts = 2;
+    // More read-only accounts than keys
+    let mut readonly_unsigned = transaction.message.clone();
+    readonly_unsigned.header.num_readonly_unsigned_accounts = u8::MAX;
+    // An account index past the keys
+    let mut out_of_range = transaction.message.clone();
+    out_of_range.instructions[0].accounts[0] = u8::MAX;
+    for message in [readonly_payer, readonly_unsigned, out_of_range] {
+        assert!(matches!(
+            zip(&message),
+            Err(ZipperClientError::InvalidTransaction)
+        ));
+    }
+}
+
+#[test]
+fn compile_zipped_v0_message() {
+    let payer = Pubkey::new_unique();
+    let user_ata = Pubkey::new_unique();
+    let rugger_ata = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let table = LookupTable {
+        key: Pubkey::new_unique(),
+        addresses: vec![mint, user_ata, rugger_ata, TOKEN_PROGRAM_ID],
+    };
+    let transfer_instruction =
+        transfer(&TOKEN_PROGRAM_ID, &user_ata, &rugger_ata, &payer, &[], 1).unwrap();
+    let zipper_instruction = verify_instruction(
+        &[payer, user_ata, mint, other],
+        vec![1, 2, 3, 4],
+        vec![],
+        vec![],
+        vec![],
+    );
+    let message = zip_v0_message(
+        &payer,
+        &[transfer_instruction.clone()],
+        &zipper_instruction,
+        &[table.clone()],
+        Hash::default(),
+    )
+    .unwrap();
+
+    // Zipped accounts found in the table are loaded through it, even the mint which only
+    // the zipper instruction uses, while invoked programs and signers stay static
+    assert_eq!(
+        message.account_keys,
+        vec![payer, TOKEN_PROGRAM_ID, PROGRAM_ID, other]
+    );
+    assert_eq!(
+        message.header,
+        MessageHeader {
+            num_required_signatures: 1,
+            num_readonly_signed_accounts: 0,
+            num_readonly_unsigned_accounts: 3,
+        }
+    );
+    assert_eq!(
+        message.address_table_lookups,
+        vec![MessageAddressTableLookup {
+            account_key: table.key,
+            writable_indexes: vec![1, 2],
+            readonly_indexes: vec![0],
+        }]
+    );
+    assert_eq!(message.instructions[0].accounts, vec![4, 5, 0]);
+    assert_eq!(message.instructions[1].accounts, vec![0, 4, 6, 3]);
+
+    // Every compiled index resolves to the key of the original instruction
+    let keys = resolve_account_keys(&message, &[table.clone()]).unwrap();
+    for (compiled, instruction) in message
+        .instructions
+        .iter()
+        .zip([&transfer_instruction, &zipper_instruction])
+    {
+        assert_eq!(
+            keys[compiled.program_id_index as usize],
+            instruction.program_id
+        );
+        let resolved: Vec<Pubkey> = compiled
+            .accounts
+            .iter()
+            .map(|index| keys[*index as usize])
+            .collect();
+        let expected: Vec<Pubkey> = instruction
+            .accounts
+            .iter()
+            .map(|meta| meta.pubkey)
+            .collect();
+        assert_eq!(resolved, expected);
+    }
+
+    // Without lookup tables, every account is static
+    let message = compile_v0_message(
+        &payer,
+        &[transfer_instruction, zipper_instruction],
+        &[],
+        Hash::default(),
+    )
+    .unwrap();
+    assert_eq!(message.account_keys.len(), 7);
+    assert!(message.address_table_lookups.is_empty());
+}
+
+#[test]
+fn deserialize_lookup_table() {
+    let key = Pubkey::new_unique();
+    let addresses = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut data = vec![0; LOOKUP_TABLE_META_SIZE];
+    data[0..4].copy_from_slice(&1u32.to_le_bytes());
+    for address in &addresses {
+        data.extend_from_slice(address.as_ref());
+    }
+    assert_eq!(
+        LookupTable::deserialize(key, &data).unwrap(),
+        LookupTable { key, addresses }
+    );
+
+    // Uninitialized and truncated tables
+    let mut uninitialized = data.clone();
+    uninitialized[0] = 0;
+    assert!(LookupTable::deserialize(key, &uninitialized).is_err());
+    assert!(LookupTable::deserialize(key, &data[..data.len() - 1]).is_err());
+}